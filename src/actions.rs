@@ -0,0 +1,615 @@
+use crate::cite;
+use crate::mail::Email;
+use crate::mute;
+use crate::search::Target;
+use crate::{Message, fonts, hint_text, secondary_text, section_heading, small_button};
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use iced::widget::{checkbox, column, row, text};
+use iced::{Element, Length, Theme};
+use serde::{Deserialize, Serialize};
+
+// Questions and requests aimed at the user, pulled out of each briefing and kept until they are
+// checked off. Open items go back into the next prompt so the model can chase them.
+
+// How an item compares with the briefing before the one it was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    #[default]
+    Unchanged,
+    New,
+    Updated,
+    Escalated,
+    Resolved,
+}
+
+impl Change {
+    pub fn label(self) -> &'static str {
+        match self {
+            Change::Unchanged => "",
+            Change::New => "NEW",
+            Change::Updated => "UPDATED",
+            Change::Escalated => "ESCALATED",
+            Change::Resolved => "RESOLVED",
+        }
+    }
+}
+
+// An item as found in one briefing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Found {
+    pub task: String,
+    pub person: String,
+    pub email_id: Option<String>,
+    pub due: Option<NaiveDate>,
+    #[serde(default)]
+    pub change: Change,
+}
+
+fn describe(person: &str, due: Option<NaiveDate>) -> String {
+    let mut parts = Vec::new();
+    if !person.is_empty() {
+        parts.push(format!("from {}", person));
+    }
+    if let Some(due) = due {
+        parts.push(format!("due {}", due.format("%b %-d")));
+    }
+
+    parts.join(" · ")
+}
+
+impl Found {
+    pub fn describe(&self) -> String {
+        describe(&self.person, self.due)
+    }
+
+    fn is(&self, task: &str, person: &str, email_id: &Option<String>) -> bool {
+        normalize(&self.task) == normalize(task)
+            && (&self.email_id == email_id || normalize(&self.person) == normalize(person))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionItem {
+    pub id: u64,
+    pub task: String,
+    pub person: String,
+    pub email_id: Option<String>,
+    pub due: Option<NaiveDate>,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    // Latest briefing that mentioned the item
+    pub briefing_id: u64,
+    pub done_at: Option<DateTime<Local>>,
+    pub snoozed_until: Option<NaiveDate>,
+}
+
+impl ActionItem {
+    pub fn is_open(&self) -> bool {
+        self.done_at.is_none()
+    }
+
+    pub fn is_snoozed(&self, today: NaiveDate) -> bool {
+        self.snoozed_until.is_some_and(|until| until > today)
+    }
+
+    pub fn describe(&self) -> String {
+        describe(&self.person, self.due)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tracker {
+    items: Vec<ActionItem>,
+    next_id: u64,
+}
+
+// Case and punctuation differ between briefings, the words do not
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+impl Tracker {
+    pub fn all(&self) -> &[ActionItem] {
+        &self.items
+    }
+
+    // Open and not snoozed, soonest due first
+    pub fn pending(&self, today: NaiveDate) -> Vec<&ActionItem> {
+        let mut pending = self
+            .items
+            .iter()
+            .filter(|item| item.is_open() && !item.is_snoozed(today))
+            .collect::<Vec<&ActionItem>>();
+        pending.sort_by_key(|item| (item.due.is_none(), item.due, item.first_seen));
+
+        pending
+    }
+
+    // Items already tracked are refreshed rather than added again; checked ones stay checked and
+    // ones the model reports resolved get checked
+    pub fn merge(&mut self, briefing_id: u64, found: &[Found], now: DateTime<Local>) {
+        for found in found {
+            if normalize(&found.task).is_empty() {
+                continue;
+            }
+
+            let known = self
+                .items
+                .iter_mut()
+                .find(|item| found.is(&item.task, &item.person, &item.email_id));
+
+            match known {
+                Some(item) => {
+                    item.last_seen = now;
+                    item.briefing_id = briefing_id;
+                    if found.due.is_some() {
+                        item.due = found.due;
+                    }
+                    if found.change == Change::Resolved && item.is_open() {
+                        item.done_at = Some(now);
+                    }
+                }
+                None if found.change == Change::Resolved => {}
+                None => {
+                    self.next_id += 1;
+                    self.items.push(ActionItem {
+                        id: self.next_id,
+                        task: found.task.trim().to_string(),
+                        person: found.person.trim().to_string(),
+                        email_id: found.email_id.clone(),
+                        due: found.due,
+                        first_seen: now,
+                        last_seen: now,
+                        briefing_id,
+                        done_at: None,
+                        snoozed_until: None,
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn set_done(&mut self, id: u64, done: bool) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.id == id) {
+            item.done_at = done.then(Local::now);
+        }
+    }
+
+    pub fn snooze(&mut self, id: u64, until: Option<NaiveDate>) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.id == id) {
+            item.snoozed_until = until;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Listed {
+    task: String,
+    #[serde(default)]
+    person: String,
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    due: String,
+    // Only asked for in delta mode
+    #[serde(default)]
+    status: String,
+}
+
+fn is_marker(line: &str) -> bool {
+    line.trim()
+        .trim_matches(|c: char| c == '*' || c == '#' || c == ':' || c.is_whitespace())
+        .eq_ignore_ascii_case("action items")
+}
+
+// The model lists action items after a line reading ACTION ITEMS, one JSON object per line.
+// Returns the briefing without that list, and the items it names.
+pub fn split_response(response: &str, emails: &[Email]) -> (String, Vec<Found>) {
+    let mut offset = 0;
+    let mut marker = None;
+    for line in response.split_inclusive('\n') {
+        if is_marker(line) {
+            marker = Some((offset, offset + line.len()));
+        }
+        offset += line.len();
+    }
+    let Some((start, end)) = marker else {
+        return (response.trim_end().to_string(), Vec::new());
+    };
+
+    let found = response[end..]
+        .lines()
+        .map(|line| line.trim().trim_start_matches("- "))
+        .filter(|line| line.starts_with('{'))
+        .filter_map(|line| serde_json::from_str::<Listed>(line).ok())
+        .map(|listed| {
+            let reference = listed.email.trim().trim_start_matches('#').to_lowercase();
+            Found {
+                task: listed.task,
+                person: listed.person,
+                email_id: emails
+                    .iter()
+                    .find(|email| !reference.is_empty() && cite::reference(&email.id) == reference)
+                    .map(|email| email.id.clone()),
+                due: NaiveDate::parse_from_str(listed.due.trim(), "%Y-%m-%d").ok(),
+                change: match listed.status.trim().to_lowercase().as_str() {
+                    "new" => Change::New,
+                    "updated" => Change::Updated,
+                    "escalated" => Change::Escalated,
+                    "resolved" => Change::Resolved,
+                    _ => Change::Unchanged,
+                },
+            }
+        })
+        .filter(|found| !found.task.trim().is_empty())
+        .collect();
+
+    (response[..start].trim_end().to_string(), found)
+}
+
+// Marks each item against the previous briefing's. What the model calls escalated or resolved is
+// kept, since only it can tell from the mail.
+pub fn mark_changes(found: &mut [Found], previous: &[Found]) {
+    for item in found {
+        let before = previous
+            .iter()
+            .find(|before| item.is(&before.task, &before.person, &before.email_id));
+
+        item.change = match (item.change, before) {
+            (Change::Escalated | Change::Resolved, _) => item.change,
+            (_, None) => Change::New,
+            (Change::Updated, Some(_)) => Change::Updated,
+            (_, Some(before)) if before.due != item.due => Change::Updated,
+            (_, Some(_)) => Change::Unchanged,
+        };
+    }
+}
+
+// Open items soonest due first, then snoozed and recently done ones, and the mute rules in force
+pub fn view<'a>(
+    tracker: &'a Tracker,
+    muted: &'a mute::Rules,
+    today: NaiveDate,
+) -> Element<'a, Message> {
+    let item_row = |item: &ActionItem, detail: String| {
+        let id = item.id;
+        row![
+            checkbox(!item.is_open()).on_toggle(move |done| Message::ActionChecked(id, done)),
+            column![
+                text(item.task.clone())
+                    .font(fonts::body())
+                    .size(fonts::scaled(14)),
+                (!detail.is_empty()).then(|| text(detail)
+                    .font(fonts::body())
+                    .size(fonts::scaled(11))
+                    .style(secondary_text)),
+            ]
+            .spacing(2)
+            .width(Length::Fill),
+            item.email_id
+                .clone()
+                .map(|email_id| small_button("Email", Message::OpenHit(Target::Email(email_id)))),
+            small_button("Copy", Message::CopyActionItem(id)),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center)
+    };
+
+    let pending = tracker.pending(today);
+    let mut snoozed = tracker
+        .all()
+        .iter()
+        .filter(|item| item.is_open() && item.is_snoozed(today))
+        .collect::<Vec<_>>();
+    snoozed.sort_by_key(|item| item.snoozed_until);
+    let mut done = tracker
+        .all()
+        .iter()
+        .filter(|item| !item.is_open())
+        .collect::<Vec<_>>();
+    done.sort_by_key(|item| std::cmp::Reverse(item.done_at));
+    done.truncate(10);
+
+    let mut list = column![section_heading("Waiting on you")].spacing(10);
+    if pending.is_empty() {
+        list = list.push(
+            text("Nothing is waiting on you")
+                .font(fonts::body())
+                .size(fonts::scaled(14)),
+        );
+    }
+    for item in pending {
+        let overdue = item.due.is_some_and(|due| due < today);
+        let mut detail = item.describe();
+        if overdue {
+            detail.push_str(" · overdue");
+        }
+        let next_week = today + Duration::days(7);
+        list = list.push(
+            row![
+                item_row(item, detail),
+                small_button("Tomorrow", Message::SnoozeAction(item.id, today.succ_opt())),
+                small_button("Next week", Message::SnoozeAction(item.id, Some(next_week))),
+            ]
+            .spacing(6)
+            .align_y(iced::Alignment::Center),
+        );
+    }
+
+    if !snoozed.is_empty() {
+        list = list.push(section_heading("Snoozed"));
+    }
+    for item in snoozed {
+        let until = item
+            .snoozed_until
+            .map(|until| format!("snoozed until {}", until.format("%b %-d")))
+            .unwrap_or_default();
+        let detail = [item.describe(), until]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<String>>()
+            .join(" · ");
+        list = list.push(
+            row![
+                item_row(item, detail),
+                small_button("Wake", Message::SnoozeAction(item.id, None)),
+            ]
+            .spacing(6)
+            .align_y(iced::Alignment::Center),
+        );
+    }
+
+    if !done.is_empty() {
+        list = list.push(section_heading("Recently done"));
+    }
+    for item in done {
+        list = list.push(item_row(item, item.describe()));
+    }
+
+    let muted = muted
+        .all()
+        .iter()
+        .filter(|rule| rule.is_active(today))
+        .collect::<Vec<&mute::Rule>>();
+    if !muted.is_empty() {
+        list = list.push(section_heading("Kept out of briefings"));
+    }
+    for rule in muted {
+        list = list.push(
+            row![
+                text(rule.describe())
+                    .font(fonts::body())
+                    .size(fonts::scaled(13))
+                    .width(Length::Fill),
+                small_button("Unmute", Message::Unmute(rule.target.clone())),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+        );
+    }
+
+    list.into()
+}
+
+// Items of one briefing, marked against the one before it
+pub fn found_view(items: &[Found]) -> Element<'_, Message> {
+    column(items.iter().map(|item| {
+        let change = item.change;
+        let badge = text(change.label())
+            .font(fonts::body())
+            .size(fonts::scaled(10))
+            .width(Length::Fixed(64.0))
+            .style(move |theme: &Theme| {
+                let palette = theme.palette();
+                text::Style {
+                    color: Some(match change {
+                        Change::New => palette.success,
+                        Change::Updated => palette.primary,
+                        Change::Escalated => palette.danger,
+                        Change::Resolved | Change::Unchanged => palette.text.scale_alpha(0.45),
+                    }),
+                }
+            });
+        let resolved = change == Change::Resolved;
+        let detail = item.describe();
+
+        row![
+            badge,
+            column![
+                text(&item.task)
+                    .font(fonts::body())
+                    .size(fonts::scaled(13))
+                    .style(if resolved { hint_text } else { text::default }),
+                (!detail.is_empty()).then(|| text(detail)
+                    .font(fonts::body())
+                    .size(fonts::scaled(11))
+                    .style(secondary_text)),
+            ]
+            .spacing(2),
+        ]
+        .spacing(8)
+        .into()
+    }))
+    .spacing(6)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(id: &str) -> Email {
+        Email {
+            id: String::from(id),
+            subject: String::new(),
+            body: String::new(),
+            from: String::new(),
+            to: String::new(),
+            cc: String::new(),
+            date: String::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    fn found(task: &str, person: &str, change: Change) -> Found {
+        Found {
+            task: String::from(task),
+            person: String::from(person),
+            email_id: None,
+            due: None,
+            change,
+        }
+    }
+
+    #[test]
+    fn split_response_without_marker_keeps_the_briefing() {
+        let (text, items) = split_response("Good morning.\n\nAll quiet.\n\n", &[]);
+
+        assert_eq!(text, "Good morning.\n\nAll quiet.");
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn split_response_reads_items_and_skips_malformed_lines() {
+        let emails = [email("<abc@mail>")];
+        let response = format!(
+            "Good morning.\n\n## Action Items:\n\
+             {{\"task\": \"Send the report\", \"person\": \"Ada\", \"email\": \"#{}\", \"due\": \"2025-01-31\"}}\n\
+             - {{\"task\": \"Book a room\", \"status\": \"Resolved\"}}\n\
+             {{\"task\": \"Broken\", \n\
+             {{\"person\": \"Nobody\"}}\n\
+             {{\"task\": \"  \"}}\n\
+             {{\"task\": \"Call back\", \"email\": \"#ffffffff\", \"due\": \"Friday\"}}\n",
+            cite::reference("<abc@mail>")
+        );
+
+        let (text, items) = split_response(&response, &emails);
+
+        assert_eq!(text, "Good morning.");
+        assert_eq!(
+            items,
+            [
+                Found {
+                    email_id: Some(String::from("<abc@mail>")),
+                    due: NaiveDate::from_ymd_opt(2025, 1, 31),
+                    ..found("Send the report", "Ada", Change::Unchanged)
+                },
+                found("Book a room", "", Change::Resolved),
+                found("Call back", "", Change::Unchanged),
+            ]
+        );
+    }
+
+    #[test]
+    fn mark_changes_compares_with_the_previous_briefing() {
+        let previous = [
+            found("Send the report", "Ada", Change::Unchanged),
+            Found {
+                due: NaiveDate::from_ymd_opt(2025, 1, 31),
+                ..found("Book a room", "Grace", Change::Unchanged)
+            },
+            found("Call back", "Bob", Change::Unchanged),
+        ];
+        let mut items = [
+            found("send the report!", "Ada", Change::Unchanged),
+            Found {
+                due: NaiveDate::from_ymd_opt(2025, 2, 3),
+                ..found("Book a room", "Grace", Change::Unchanged)
+            },
+            found("Call back", "Bob", Change::Escalated),
+            found("Review the draft", "Ada", Change::Updated),
+        ];
+
+        mark_changes(&mut items, &previous);
+
+        assert_eq!(
+            items.map(|item| item.change),
+            [
+                Change::Unchanged,
+                Change::Updated,
+                Change::Escalated,
+                Change::New
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_refreshes_known_items_instead_of_adding_them() {
+        let mut tracker = Tracker::default();
+        let then = Local::now() - Duration::days(1);
+        let now = Local::now();
+
+        tracker.merge(1, &[found("Send the report", "Ada", Change::New)], then);
+        tracker.merge(
+            2,
+            &[Found {
+                due: NaiveDate::from_ymd_opt(2025, 1, 31),
+                ..found("Send the Report.", "ada", Change::Unchanged)
+            }],
+            now,
+        );
+
+        let [item] = tracker.all() else {
+            panic!("expected one item, got {:?}", tracker.all());
+        };
+        assert_eq!(item.briefing_id, 2);
+        assert_eq!(item.first_seen, then);
+        assert_eq!(item.last_seen, now);
+        assert_eq!(item.due, NaiveDate::from_ymd_opt(2025, 1, 31));
+    }
+
+    #[test]
+    fn merge_closes_resolved_items_and_keeps_checked_ones_closed() {
+        let mut tracker = Tracker::default();
+        let now = Local::now();
+
+        tracker.merge(
+            1,
+            &[
+                found("Send the report", "Ada", Change::New),
+                found("Book a room", "Grace", Change::New),
+            ],
+            now,
+        );
+        tracker.set_done(2, true);
+
+        // The model resolves one, mentions the checked one again and resolves one never tracked
+        tracker.merge(
+            2,
+            &[
+                found("Send the report", "Ada", Change::Resolved),
+                found("Book a room", "Grace", Change::Unchanged),
+                found("Water the plants", "", Change::Resolved),
+            ],
+            now,
+        );
+
+        assert_eq!(tracker.all().len(), 2);
+        assert!(tracker.all().iter().all(|item| !item.is_open()));
+        assert!(tracker.pending(now.date_naive()).is_empty());
+    }
+
+    #[test]
+    fn reopened_item_is_pending_again() {
+        let mut tracker = Tracker::default();
+        let now = Local::now();
+
+        tracker.merge(1, &[found("Send the report", "Ada", Change::New)], now);
+        tracker.set_done(1, true);
+        tracker.set_done(1, false);
+        tracker.merge(
+            2,
+            &[found("Send the report", "Ada", Change::Unchanged)],
+            now,
+        );
+
+        assert_eq!(tracker.all().len(), 1);
+        assert_eq!(tracker.pending(now.date_naive())[0].briefing_id, 2);
+    }
+}
@@ -1,15 +1,21 @@
+use crate::config::{Llm, Provider};
+use crate::job::{Progress, Stage};
+use crate::usage::Usage;
 use serde::{Deserialize, Serialize};
-use std::env;
+
+pub const DEFAULT_MODEL: &str = "gemini-2.5-flash";
 
 // Structure for request and response
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Content {
+    #[serde(default)]
     pub parts: Vec<Part>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Part {
+    #[serde(default)]
     pub text: String,
 }
 
@@ -31,8 +37,29 @@ impl GeminiRequest {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GeminiResponse {
+    #[serde(default)]
     pub candidates: Vec<Candidate>,
+    pub usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UsageMetadata {
+    pub prompt_token_count: u64,
+    pub candidates_token_count: u64,
+    pub total_token_count: u64,
+}
+
+impl From<UsageMetadata> for Usage {
+    fn from(metadata: UsageMetadata) -> Self {
+        Self {
+            prompt_tokens: metadata.prompt_token_count,
+            candidate_tokens: metadata.candidates_token_count,
+            total_tokens: metadata.total_token_count,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -52,13 +79,98 @@ impl GeminiResponse {
     }
 }
 
-pub async fn generate_response(prompt: String) -> Result<String, String> {
-    let api_key =
-        env::var("GEMINI_API_KEY").map_err(|e| format!("Failed to get GEMINI_API_KEY: {}", e))?;
+// Ollama's generate endpoint, streamed as one object per line
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    prompt: String,
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+pub async fn generate_response(
+    llm: &Llm,
+    prompt: String,
+    progress: &Progress,
+) -> Result<(String, Usage), String> {
+    match llm.provider {
+        Provider::Gemini => generate_gemini(llm, prompt, progress).await,
+        Provider::Ollama => generate_ollama(llm, prompt, progress).await,
+    }
+}
+
+// Sent as a header rather than in the URL, which request errors print
+fn gemini_key(llm: &Llm) -> Result<String, String> {
+    let api_key = llm.api_key.reveal()?;
+    if api_key.is_empty() {
+        return Err(String::from("Gemini API key not set"));
+    }
+
+    Ok(api_key)
+}
+
+// Feeds each complete line of a streamed response body to on_line
+async fn read_lines(
+    mut response: reqwest::Response,
+    mut on_line: impl FnMut(&str) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut buffer = Vec::new();
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?
+    {
+        buffer.extend_from_slice(&chunk);
+        while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line = buffer.drain(..=end).collect::<Vec<u8>>();
+            on_line(String::from_utf8_lossy(&line).trim())?;
+        }
+    }
+    if !buffer.is_empty() {
+        on_line(String::from_utf8_lossy(&buffer).trim())?;
+    }
+
+    Ok(())
+}
+
+async fn generate_gemini(
+    llm: &Llm,
+    prompt: String,
+    progress: &Progress,
+) -> Result<(String, Usage), String> {
+    let api_key = gemini_key(llm)?;
     let client = reqwest::Client::new();
     let request = GeminiRequest::new(prompt);
 
-    let response = client.post(format!("https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:generateContent?key={}", api_key))
+    let response = client
+        .post(format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse",
+            llm.model
+        ))
+        .header("x-goog-api-key", api_key)
         .json(&request)
         .send()
         .await
@@ -66,12 +178,132 @@ pub async fn generate_response(prompt: String) -> Result<String, String> {
 
     // Check if the response is successful
     if !response.status().is_success() {
-        Err(String::from("Failed to parse response"))
-    } else {
-        let response = response
-            .json::<GeminiResponse>()
-            .await
+        return Err(format!("Gemini returned {}", response.status()));
+    }
+
+    // Server-sent events, each carrying the next piece of text; usage comes with the last one
+    let mut text = String::new();
+    let mut usage = Usage::default();
+    read_lines(response, |line| {
+        let Some(data) = line.strip_prefix("data:") else {
+            return Ok(());
+        };
+        let event = serde_json::from_str::<GeminiResponse>(data.trim())
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        if let Some(piece) = event.first_text() {
+            text.push_str(&piece);
+        }
+        if let Some(metadata) = event.usage_metadata {
+            usage = metadata.into();
+        }
+        progress.report(Stage::Streaming(text.chars().count()));
+
+        Ok(())
+    })
+    .await?;
+
+    if text.is_empty() {
+        return Err(String::from("No text generated"));
+    }
+
+    Ok((text, usage))
+}
+
+async fn generate_ollama(
+    llm: &Llm,
+    prompt: String,
+    progress: &Progress,
+) -> Result<(String, Usage), String> {
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/api/generate",
+            llm.ollama_url.trim_end_matches('/')
+        ))
+        .json(&OllamaRequest {
+            model: &llm.model,
+            prompt,
+            stream: true,
+        })
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Ollama returned {}", response.status()));
+    }
+
+    // One JSON object per line; the final one has the token counts
+    let mut text = String::new();
+    let mut usage = Usage::default();
+    read_lines(response, |line| {
+        if line.is_empty() {
+            return Ok(());
+        }
+        let chunk = serde_json::from_str::<OllamaResponse>(line)
             .map_err(|e| format!("Failed to parse response: {}", e))?;
-        response.first_text().ok_or("No text generated".to_string())
+
+        text.push_str(&chunk.response);
+        if chunk.done {
+            usage = Usage {
+                prompt_tokens: chunk.prompt_eval_count,
+                candidate_tokens: chunk.eval_count,
+                total_tokens: chunk.prompt_eval_count + chunk.eval_count,
+            };
+        }
+        progress.report(Stage::Streaming(text.chars().count()));
+
+        Ok(())
+    })
+    .await?;
+
+    // An empty answer is a failure, so the offline summary takes over
+    if text.trim().is_empty() {
+        return Err(String::from("No text generated"));
+    }
+
+    Ok((text, usage))
+}
+
+// Looks up the configured model, which validates the key without spending tokens
+pub async fn check_connection(llm: &Llm) -> Result<String, String> {
+    match llm.provider {
+        Provider::Gemini => {
+            let response = reqwest::Client::new()
+                .get(format!(
+                    "https://generativelanguage.googleapis.com/v1beta/models/{}",
+                    llm.model
+                ))
+                .header("x-goog-api-key", gemini_key(llm)?)
+                .send()
+                .await
+                .map_err(|e| format!("Failed to send request: {}", e))?;
+
+            if response.status().is_success() {
+                Ok(format!("{} is available", llm.model))
+            } else {
+                Err(format!("{} returned {}", llm.model, response.status()))
+            }
+        }
+        Provider::Ollama => {
+            let tags = reqwest::Client::new()
+                .get(format!("{}/api/tags", llm.ollama_url.trim_end_matches('/')))
+                .send()
+                .await
+                .map_err(|e| format!("Failed to reach Ollama: {}", e))?
+                .json::<OllamaTags>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+            // Ollama lists "llama3.1" as "llama3.1:latest"
+            let installed = tags.models.iter().any(|model| {
+                model.name == llm.model || model.name == format!("{}:latest", llm.model)
+            });
+            if installed {
+                Ok(format!("{} is available", llm.model))
+            } else {
+                Err(format!("{} is not installed in Ollama", llm.model))
+            }
+        }
     }
 }
@@ -0,0 +1,63 @@
+use crate::mail::Email;
+use directories::ProjectDirs;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+
+// Local copy of every fetched email, keyed by email ID
+
+fn archive_dir() -> Result<PathBuf, String> {
+    let project_dirs = ProjectDirs::from("com", "Apex", "tit-babbler")
+        .ok_or("Could not determine project directory")?;
+    let archive_dir = project_dirs.data_dir().join("emails");
+
+    fs::create_dir_all(&archive_dir)
+        .map_err(|e| format!("Failed to create email archive: {}", e))?;
+
+    Ok(archive_dir)
+}
+
+// Message-IDs may contain characters that are not valid in file names
+fn file_name(id: &str) -> String {
+    let digest = Sha256::digest(id.as_bytes());
+    let name = digest
+        .iter()
+        .take(16)
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    format!("{}.json", name)
+}
+
+pub fn store(emails: &[Email]) -> Result<(), String> {
+    let archive_dir = archive_dir()?;
+
+    for email in emails {
+        let json = serde_json::to_string(email)
+            .map_err(|e| format!("Failed to serialize email: {}", e))?;
+        fs::write(archive_dir.join(file_name(&email.id)), json)
+            .map_err(|e| format!("Failed to archive email: {}", e))?;
+    }
+
+    Ok(())
+}
+
+pub fn load(id: &str) -> Option<Email> {
+    let content = fs::read_to_string(archive_dir().ok()?.join(file_name(id))).ok()?;
+
+    serde_json::from_str(&content).ok()
+}
+
+pub fn load_all() -> Vec<Email> {
+    let Ok(entries) = archive_dir().and_then(|dir| {
+        fs::read_dir(dir).map_err(|e| format!("Failed to read email archive: {}", e))
+    }) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect()
+}
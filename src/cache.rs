@@ -0,0 +1,140 @@
+use crate::mail::Email;
+use crate::usage::Usage;
+use chrono::{DateTime, Local};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub text: String,
+    pub model: String,
+    pub usage: Usage,
+    pub created_at: DateTime<Local>,
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Covers what the answer is asked about: the mail, the template and, for a delta briefing, the
+// briefing it is compared against. Open items, project memory and the date change after every
+// refresh, so including them would mean the same inbox never hits the cache.
+pub fn key(emails: &[Email], template: &str, model: &str, baseline: Option<u64>) -> String {
+    let mut normalized = emails
+        .iter()
+        .map(|email| {
+            format!(
+                "{}\n{}\n{}",
+                normalize(&email.from),
+                normalize(&email.subject),
+                normalize(&email.body)
+            )
+        })
+        .collect::<Vec<String>>();
+    normalized.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(normalize(template).as_bytes());
+    hasher.update([0]);
+    hasher.update(baseline.map(|id| id.to_string()).unwrap_or_default());
+    for email in normalized {
+        hasher.update([0]);
+        hasher.update(email.as_bytes());
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn cache_file(key: &str) -> Option<PathBuf> {
+    let project_dirs = ProjectDirs::from("com", "Apex", "tit-babbler")?;
+    let cache_dir = project_dirs.cache_dir().join("responses");
+
+    fs::create_dir_all(&cache_dir).ok()?;
+
+    Some(cache_dir.join(format!("{}.json", key)))
+}
+
+pub fn load(key: &str) -> Option<CachedResponse> {
+    let content = fs::read_to_string(cache_file(key)?).ok()?;
+
+    serde_json::from_str(&content).ok()
+}
+
+// A failed write only costs us a future cache miss, so it is not surfaced as an error
+pub fn store(key: &str, response: &CachedResponse) {
+    let Some(path) = cache_file(key) else {
+        return;
+    };
+
+    match serde_json::to_string(response) {
+        Ok(json) => {
+            if let Err(e) = fs::write(path, json) {
+                eprintln!("Failed to write response cache: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Failed to serialize cached response: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(id: &str, from: &str, subject: &str, body: &str) -> Email {
+        Email {
+            id: String::from(id),
+            subject: String::from(subject),
+            body: String::from(body),
+            from: String::from(from),
+            to: String::from("me@example.com"),
+            cc: String::new(),
+            date: String::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    fn inbox() -> Vec<Email> {
+        vec![
+            email("1", "Ada <ada@example.com>", "Report", "Due on Friday."),
+            email("2", "Grace <grace@example.com>", "Lunch", "Noon?"),
+        ]
+    }
+
+    #[test]
+    fn same_mail_gives_same_key_across_refreshes() {
+        let first = key(&inbox(), "Summarize.", "gemini", None);
+
+        // Fetched again in another order and with the body rewrapped
+        let mut again = inbox();
+        again.reverse();
+        again[1].body = String::from("Due  on\nFriday.");
+
+        assert_eq!(first, key(&again, "Summarize.", "gemini", None));
+    }
+
+    #[test]
+    fn mail_template_model_and_baseline_change_the_key() {
+        let base = key(&inbox(), "Summarize.", "gemini", None);
+
+        let mut more = inbox();
+        more.push(email("3", "Bob <bob@example.com>", "Hi", "Hello there."));
+
+        assert_ne!(base, key(&more, "Summarize.", "gemini", None));
+        assert_ne!(base, key(&inbox(), "Summarize briefly.", "gemini", None));
+        assert_ne!(base, key(&inbox(), "Summarize.", "llama3", None));
+        assert_ne!(base, key(&inbox(), "Summarize.", "gemini", Some(4)));
+        assert_ne!(
+            key(&inbox(), "Summarize.", "gemini", Some(4)),
+            key(&inbox(), "Summarize.", "gemini", Some(5))
+        );
+    }
+}
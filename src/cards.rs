@@ -0,0 +1,186 @@
+use crate::history::Briefing;
+use crate::mail::Email;
+use crate::{Message, actions, cite, fallback, fonts, hint_text, mute, small_button};
+use chrono::{Duration, NaiveDate};
+use iced::widget::{column, container, row, text, text_editor, text_input};
+use iced::{Border, Element, Length, Padding, Theme};
+
+// Handled emails stay out this long, well past any briefing window
+pub const HANDLED_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Copy)]
+pub enum CardAction {
+    MuteThread,
+    MuteSender,
+    Snooze(NaiveDate),
+    Handled,
+    Undo,
+}
+
+// One topic of the shown briefing: its text, selectable, and the emails it cites
+#[derive(Debug, Clone)]
+pub struct Card {
+    pub text: text_editor::Content,
+    // Source numbers with the references they stand for
+    pub sources: Vec<(usize, String)>,
+    // The cited emails still in the archive, or for a paragraph citing nothing, those it names
+    // by subject or sender
+    pub emails: Vec<Email>,
+    // Typed date to snooze the topic until
+    pub snooze: String,
+    // Only about mail the previous daily briefing did not cover
+    pub new: bool,
+}
+
+// A subject or a sender's full name in the text ties it to the email without a citation
+fn mentions(paragraph: &str, email: &Email) -> bool {
+    let paragraph = paragraph.to_lowercase();
+    let subject = mute::thread_key(&email.subject);
+    let name = fallback::display_name(&email.from).to_lowercase();
+
+    (subject.len() >= 8 && paragraph.contains(&subject))
+        || (name.contains(' ') && !name.contains('@') && paragraph.contains(&name))
+}
+
+impl Card {
+    pub fn new(briefing: &Briefing, emails: &[Email], paragraph: &str, numbered: &str) -> Self {
+        let mut sources = cite::pieces(paragraph)
+            .into_iter()
+            .filter_map(|piece| match piece {
+                cite::Piece::Citation(reference) => {
+                    cite::source(briefing, &reference).map(|(number, _)| (number, reference))
+                }
+                cite::Piece::Text(_) => None,
+            })
+            .collect::<Vec<(usize, String)>>();
+        sources.sort();
+        sources.dedup();
+
+        let emails = if sources.is_empty() {
+            emails
+                .iter()
+                .filter(|email| mentions(paragraph, email))
+                .cloned()
+                .collect()
+        } else {
+            sources
+                .iter()
+                .filter_map(|(_, reference)| cite::source(briefing, reference))
+                .filter_map(|(_, id)| emails.iter().find(|email| email.id == id).cloned())
+                .collect()
+        };
+
+        Self {
+            text: text_editor::Content::with_text(numbered),
+            sources,
+            emails,
+            snooze: String::new(),
+            new: false,
+        }
+    }
+}
+
+// A card per topic: selectable text, links to the emails it cites numbered like the exported
+// sources, and actions that keep those emails out of later briefings
+pub fn view<'a>(
+    cards: &'a [Card],
+    muted: &'a mute::Rules,
+    today: NaiveDate,
+) -> Element<'a, Message> {
+    column(cards.iter().enumerate().map(|(index, card)| {
+        let rule = card
+            .emails
+            .iter()
+            .find_map(|email| muted.matching(email, today));
+        let quiet = rule.is_some();
+
+        let body = text_editor(&card.text)
+            .font(fonts::body())
+            .size(fonts::scaled(16))
+            .padding(0)
+            .on_action(move |action| Message::CardEdited(index, action))
+            .style(move |theme: &Theme, _status| text_editor::Style {
+                background: iced::Color::TRANSPARENT.into(),
+                border: Border::default(),
+                placeholder: theme.palette().text.scale_alpha(0.45),
+                value: theme
+                    .palette()
+                    .text
+                    .scale_alpha(if quiet { 0.45 } else { 1.0 }),
+                selection: theme.palette().primary.scale_alpha(0.3),
+            });
+        let act = |label: &str, action: CardAction| {
+            small_button(label.to_string(), Message::CardAction(index, action))
+        };
+        let badge: Option<Element<Message>> = card.new.then(|| {
+            text(actions::Change::New.label())
+                .font(fonts::body())
+                .size(fonts::scaled(10))
+                .style(|theme: &Theme| text::Style {
+                    color: Some(theme.palette().success),
+                })
+                .into()
+        });
+        let mut links =
+            row(badge
+                .into_iter()
+                .chain(card.sources.iter().map(|(number, reference)| {
+                    small_button(
+                        format!("[{}]", number),
+                        Message::OpenSource(reference.clone()),
+                    )
+                    .into()
+                })))
+            .spacing(4)
+            .align_y(iced::Alignment::Center);
+        links = match rule {
+            Some(rule) => links
+                .push(
+                    text(rule.describe())
+                        .font(fonts::body())
+                        .size(fonts::scaled(11))
+                        .style(hint_text),
+                )
+                .push(act("Undo", CardAction::Undo)),
+            None if card.emails.is_empty() => links,
+            None => links
+                .push(act("Mute thread", CardAction::MuteThread))
+                .push(act("Mute sender", CardAction::MuteSender))
+                .push(act(
+                    "Snooze a day",
+                    CardAction::Snooze(today + Duration::days(1)),
+                ))
+                .push(act(
+                    "Snooze a week",
+                    CardAction::Snooze(today + Duration::days(7)),
+                ))
+                .push(
+                    text_input("Snooze until...", &card.snooze)
+                        .font(fonts::body())
+                        .size(fonts::scaled(11))
+                        .padding(Padding::from([2.0, 6.0]))
+                        .width(Length::Fixed(120.0))
+                        .on_input(move |query| Message::CardSnoozeChanged(index, query))
+                        .on_submit(Message::CardSnoozeSubmitted(index)),
+                )
+                .push(act("Handled", CardAction::Handled)),
+        };
+        // Every card can be copied, including paragraphs that cite nothing
+        links = links.push(small_button("Copy", Message::CopyCard(index)));
+
+        container(column![body, links.wrap()].spacing(8))
+            .padding(12)
+            .width(Length::Fill)
+            .style(|theme: &Theme| container::Style {
+                background: Some(theme.palette().text.scale_alpha(0.03).into()),
+                border: Border {
+                    radius: iced::border::Radius::from(6.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .into()
+    }))
+    .spacing(12)
+    .into()
+}
@@ -0,0 +1,90 @@
+use crate::history::Briefing;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::sync::LazyLock;
+
+// The model cites the emails behind each statement as [#1a2b3c4d]. Message-IDs are long and
+// easy to mangle, so each email is shown to the model under a short reference instead.
+
+static CITATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[\s*#[0-9a-fA-F]{6,}(?:\s*,\s*#[0-9a-fA-F]{6,})*\s*\]").unwrap()
+});
+
+static REFERENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"#([0-9a-fA-F]{6,})").unwrap());
+
+pub fn reference(id: &str) -> String {
+    Sha256::digest(id.as_bytes())
+        .iter()
+        .take(4)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece<'a> {
+    Text(&'a str),
+    Citation(String),
+}
+
+// Splits text into plain runs and the references cited between them
+pub fn pieces(text: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut last = 0;
+
+    for citation in CITATION.find_iter(text) {
+        if citation.start() > last {
+            pieces.push(Piece::Text(&text[last..citation.start()]));
+        }
+        for reference in REFERENCE.captures_iter(citation.as_str()) {
+            pieces.push(Piece::Citation(reference[1].to_lowercase()));
+        }
+        last = citation.end();
+    }
+    if last < text.len() {
+        pieces.push(Piece::Text(&text[last..]));
+    }
+
+    pieces
+}
+
+// Position in the briefing's sources, counted from one like the exported source lists
+pub fn source(briefing: &Briefing, reference: &str) -> Option<(usize, String)> {
+    briefing
+        .source_email_ids
+        .iter()
+        .enumerate()
+        .find(|(_, id)| self::reference(id) == reference)
+        .map(|(index, id)| (index + 1, id.clone()))
+}
+
+// Replaces citations with source numbers, dropping any that match no source
+pub fn numbered(briefing: &Briefing, text: &str) -> String {
+    pieces(text)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => text.to_string(),
+            Piece::Citation(reference) => source(briefing, &reference)
+                .map(|(number, _)| format!("[{}]", number))
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+// Drops citations along with the space before them, for text the model reads again
+pub fn strip(text: &str) -> String {
+    pieces(text)
+        .into_iter()
+        .filter_map(|piece| match piece {
+            Piece::Text(text) => Some(text),
+            Piece::Citation(_) => None,
+        })
+        .fold(String::new(), |mut stripped, text| {
+            if text.starts_with(|c: char| c.is_ascii_punctuation() || c == '\n') {
+                stripped.truncate(stripped.trim_end_matches([' ', '\t']).len());
+            }
+            stripped.push_str(text);
+            stripped
+        })
+        .trim_end()
+        .to_string()
+}
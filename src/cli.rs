@@ -0,0 +1,395 @@
+use crate::config::{Config, Profile};
+use crate::export::{self, Format};
+use crate::history::{Filter, History, Kind};
+use crate::job::Progress;
+use crate::mail::Email;
+use crate::{Tits, ai, archive, mail, refresh_inbox, secrets, smtp, usage};
+use clap::{Parser, Subcommand};
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about = "Briefings from your inbox")]
+pub struct Cli {
+    /// Settings profile from config.toml to use instead of the active one
+    #[arg(short, long, global = true)]
+    pub profile: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Fetch mail, write a new briefing and print it
+    Brief {
+        /// Write the briefing to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Ignore the response cache and ask the model again
+        #[arg(long)]
+        force: bool,
+        /// Report only what changed since the previous briefing
+        #[arg(long, overrides_with = "no_delta")]
+        delta: bool,
+        /// Write a full briefing even when the config asks for changes only
+        #[arg(long, overrides_with = "delta")]
+        no_delta: bool,
+        /// Kind of briefing to write
+        #[arg(short, long, value_enum, default_value = "morning")]
+        kind: Kind,
+    },
+    /// List stored briefings
+    History {
+        /// Only list briefings of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<Kind>,
+    },
+    /// Print a stored briefing
+    Show { id: u64 },
+    /// Verify IMAP and model connectivity
+    Check,
+    /// Export a stored briefing, the latest one by default
+    Export {
+        id: Option<u64>,
+        #[arg(short, long, value_enum, default_value = "markdown")]
+        format: Format,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Manage the encrypted vault that settings can take passwords and keys from
+    Vault {
+        #[command(subcommand)]
+        action: VaultAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum VaultAction {
+    /// List entry names
+    List,
+    /// Store a secret read from stdin under this name
+    Set { name: String },
+    /// Delete an entry
+    Remove { name: String },
+}
+
+pub fn run(command: Command, profile: Option<String>) -> Result<(), String> {
+    let runtime = tokio::runtime::Runtime::new()
+        .map_err(|e| format!("Failed to start async runtime: {}", e))?;
+
+    match command {
+        Command::Brief {
+            output,
+            force,
+            delta,
+            no_delta,
+            kind,
+        } => {
+            let config = Config::load();
+            let delta = !no_delta && (delta || config.delta);
+            runtime.block_on(brief(resolve(profile)?, config, kind, output, force, delta))
+        }
+        Command::History { kind } => history(kind.map_or(Filter::All, Filter::Only)),
+        Command::Show { id } => show(id),
+        Command::Check => runtime.block_on(check(resolve(profile)?)),
+        Command::Export { id, format, output } => export(id, format, output),
+        Command::Vault { action } => vault(action),
+    }
+}
+
+fn resolve(profile: Option<String>) -> Result<Profile, String> {
+    let config = Config::read()?;
+    let name = profile.unwrap_or_else(|| config.active_name());
+    let profile = config.resolve(&name)?;
+
+    if profile.uses_vault() || smtp::uses_vault() {
+        unlock_vault()?;
+    }
+
+    Ok(profile)
+}
+
+// Reads a line from stdin, without echo when it is a terminal
+fn read_hidden(prompt: &str) -> Result<String, String> {
+    let terminal = std::io::stdin().is_terminal();
+    if terminal {
+        eprint!("{}: ", prompt);
+        set_echo(false);
+    }
+
+    let mut line = String::new();
+    let result = std::io::stdin().read_line(&mut line);
+
+    if terminal {
+        set_echo(true);
+        eprintln!();
+    }
+    result.map_err(|e| format!("Failed to read {}: {}", prompt.to_lowercase(), e))?;
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(unix)]
+fn set_echo(on: bool) {
+    let _ = std::process::Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .status();
+}
+
+#[cfg(not(unix))]
+fn set_echo(_on: bool) {}
+
+fn unlock_vault() -> Result<(), String> {
+    if secrets::is_unlocked() {
+        return Ok(());
+    }
+
+    if secrets::vault_exists() {
+        return secrets::unlock(read_hidden("Vault passphrase")?);
+    }
+
+    let passphrase = read_hidden("New vault passphrase")?;
+    if passphrase.is_empty() {
+        return Err(String::from("The vault passphrase cannot be empty"));
+    }
+    if read_hidden("Repeat the passphrase")? != passphrase {
+        return Err(String::from("The passphrases do not match"));
+    }
+
+    secrets::unlock(passphrase)
+}
+
+fn write_output(text: &str, output: Option<PathBuf>) -> Result<(), String> {
+    match output {
+        Some(path) => {
+            fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+        }
+        None => {
+            println!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+async fn brief(
+    profile: Profile,
+    config: Config,
+    kind: Kind,
+    output: Option<PathBuf>,
+    force: bool,
+    delta: bool,
+) -> Result<(), String> {
+    let (mut state, notice) = Tits::recover();
+    if let Some(notice) = notice {
+        eprintln!("{}", notice);
+    }
+    // The weekly digest reads its window from the config when building the prompt context
+    let window_hours = config.briefings.get(kind).window_hours;
+    state.config = config;
+
+    if let Some(budget) = usage::monthly_budget()
+        && usage::monthly_total(&state.usage_log) >= budget
+    {
+        return Err(format!("Monthly budget of ${:.2} reached", budget));
+    }
+
+    let problems = profile.problems();
+    if !problems.is_empty() {
+        return Err(format!("Settings are incomplete: {}", problems.join("; ")));
+    }
+    for warning in profile.warnings() {
+        eprintln!("{}", warning);
+    }
+
+    // The previous briefing is needed before refreshing, to compare against or to digest
+    state.history = History::load();
+    state.import_legacy_briefings();
+
+    let account = profile.account.clone();
+    let briefing = refresh_inbox(
+        profile,
+        kind,
+        window_hours,
+        force,
+        state.prompt_context(kind, delta),
+        state.muted.clone(),
+        Progress::default(),
+    )
+    .await?;
+    if let Some(reason) = &briefing.degraded {
+        eprintln!(
+            "Offline summary: the assistant could not be reached ({})",
+            reason
+        );
+    }
+
+    // Nothing arrived in the window, or the mail was briefed already: print what is stored
+    // without recording or delivering it again
+    if briefing.is_empty() {
+        eprintln!("No new mail");
+        return Ok(());
+    }
+    if briefing.cached
+        && let Some(stored) = state
+            .history
+            .original(&briefing)
+            .and_then(|id| state.history.get(id))
+    {
+        eprintln!("No new mail, showing briefing {}", stored.id);
+        return write_output(&export::text(stored), output);
+    }
+
+    let text = export::text(&briefing);
+    state.usage_log.extend(briefing.usage.clone());
+
+    let action_items = briefing.action_items.clone();
+    let updates = briefing.projects.clone();
+    let created_at = briefing.created_at;
+    let id = state.history.push(briefing)?;
+    state.actions.merge(id, &action_items, created_at);
+    state.projects.merge(id, &updates, created_at);
+    if let Some(briefing) = state.history.get(id) {
+        let emails = briefing
+            .source_email_ids
+            .iter()
+            .filter_map(|id| archive::load(id))
+            .collect::<Vec<Email>>();
+        state.contacts.suggest(&emails);
+    }
+    state.selected = Some(id);
+    state.write_state()?;
+
+    write_output(&text, output)?;
+
+    // A cached briefing has already been delivered
+    match state.history.get(id) {
+        Some(briefing) if smtp::enabled() && !briefing.cached => {
+            let recipients = smtp::send_briefing(account, briefing.clone()).await?;
+            eprintln!("Emailed to {} recipients", recipients);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn history(filter: Filter) -> Result<(), String> {
+    for briefing in History::load()
+        .all()
+        .iter()
+        .filter(|briefing| filter.matches(briefing))
+    {
+        let cost = briefing
+            .usage
+            .as_ref()
+            .map(|usage| format!("${:.4}", usage.cost))
+            .unwrap_or_else(|| String::from("-"));
+        let first_line = briefing.text.lines().next().unwrap_or_default();
+
+        println!(
+            "{:>5}  {}  {:<10}  {:<22}  {:>8}  {}",
+            briefing.id,
+            briefing.created_at.format("%Y-%m-%d %H:%M"),
+            briefing.kind.to_string(),
+            briefing.model,
+            cost,
+            first_line.chars().take(60).collect::<String>()
+        );
+    }
+
+    Ok(())
+}
+
+fn show(id: u64) -> Result<(), String> {
+    let history = History::load();
+    let briefing = history
+        .get(id)
+        .ok_or_else(|| format!("No briefing with id {}", id))?;
+
+    print!("{}", export::text(briefing));
+
+    Ok(())
+}
+
+async fn check(profile: Profile) -> Result<(), String> {
+    let (imap, llm) = tokio::join!(
+        mail::check_connection(&profile.account),
+        ai::check_connection(&profile.llm)
+    );
+    let mut checks = vec![("IMAP", imap), ("LLM", llm)];
+    if smtp::enabled() {
+        checks.push((
+            "SMTP",
+            smtp::check_connection(profile.account.clone()).await,
+        ));
+    }
+    let mut failed = false;
+
+    for (name, result) in checks {
+        match result {
+            Ok(detail) => println!("{:<5} ok      {}", name, detail),
+            Err(e) => {
+                failed = true;
+                println!("{:<5} failed  {}", name, e);
+            }
+        }
+    }
+
+    if failed {
+        Err(String::from("Connectivity check failed"))
+    } else {
+        Ok(())
+    }
+}
+
+fn vault(action: VaultAction) -> Result<(), String> {
+    unlock_vault()?;
+
+    match action {
+        VaultAction::List => {
+            for name in secrets::vault_names()? {
+                println!("{}", name);
+            }
+        }
+        VaultAction::Set { name } => {
+            let value = read_hidden("Secret")?;
+            if value.is_empty() {
+                return Err(String::from("Nothing to store"));
+            }
+            secrets::vault_set(&name, value)?;
+            eprintln!("Stored \"{}\"", name);
+        }
+        VaultAction::Remove { name } => {
+            if !secrets::vault_remove(&name)? {
+                return Err(format!("The vault has no entry \"{}\"", name));
+            }
+            eprintln!("Removed \"{}\"", name);
+        }
+    }
+
+    Ok(())
+}
+
+fn export(id: Option<u64>, format: Format, output: Option<PathBuf>) -> Result<(), String> {
+    let history = History::load();
+    let briefing = match id {
+        Some(id) => history.get(id),
+        None => history.latest(Filter::All),
+    }
+    .ok_or_else(|| match id {
+        Some(id) => format!("No briefing with id {}", id),
+        None => String::from("No briefings yet"),
+    })?;
+
+    match output {
+        Some(path) => {
+            let path = export::export(briefing, format, Some(path))?;
+            eprintln!("Exported to {}", path.display());
+            Ok(())
+        }
+        None => std::io::stdout()
+            .write_all(&export::render(briefing, format))
+            .map_err(|e| format!("Failed to write to stdout: {}", e)),
+    }
+}
@@ -0,0 +1,607 @@
+use crate::history;
+use crate::secrets::{Kind, Secret};
+use crate::{ai, persist, prompt, schedule};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+// Settings edited in the app and stored in config.toml as named profiles. Environment variables
+// override individual keys of the profile in use, so an existing .env keeps working.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Account {
+    pub server: String,
+    pub port: u16,
+    pub username: String,
+    pub password: Secret,
+    pub folders: Vec<String>,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Self {
+            server: String::new(),
+            port: 993,
+            username: String::new(),
+            password: Secret::default(),
+            folders: vec![String::from("INBOX")],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    Gemini,
+    Ollama,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 2] = [Provider::Gemini, Provider::Ollama];
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Provider::Gemini => "Gemini",
+            Provider::Ollama => "Ollama (local)",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Llm {
+    pub provider: Provider,
+    pub model: String,
+    pub api_key: Secret,
+    pub ollama_url: String,
+}
+
+impl Default for Llm {
+    fn default() -> Self {
+        Self {
+            provider: Provider::Gemini,
+            model: String::new(),
+            api_key: Secret::default(),
+            ollama_url: String::from("http://localhost:11434"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    System,
+}
+
+impl ThemeName {
+    pub const ALL: [ThemeName; 3] = [ThemeName::Dark, ThemeName::Light, ThemeName::System];
+
+    // None lets iced follow the system's light or dark preference
+    pub fn theme(self) -> Option<iced::Theme> {
+        match self {
+            ThemeName::Dark => Some(iced::Theme::Dark),
+            ThemeName::Light => Some(iced::Theme::Light),
+            ThemeName::System => None,
+        }
+    }
+}
+
+impl fmt::Display for ThemeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ThemeName::Dark => "Dark",
+            ThemeName::Light => "Light",
+            ThemeName::System => "Follow system",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextSize {
+    Small,
+    #[default]
+    Normal,
+    Large,
+    Larger,
+}
+
+impl TextSize {
+    pub const ALL: [TextSize; 4] = [
+        TextSize::Small,
+        TextSize::Normal,
+        TextSize::Large,
+        TextSize::Larger,
+    ];
+
+    // Size of the briefing text; everything else scales with it
+    pub fn points(self) -> f32 {
+        match self {
+            TextSize::Small => 14.0,
+            TextSize::Normal => 16.0,
+            TextSize::Large => 18.0,
+            TextSize::Larger => 21.0,
+        }
+    }
+}
+
+impl fmt::Display for TextSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TextSize::Small => "Small",
+            TextSize::Normal => "Normal",
+            TextSize::Large => "Large",
+            TextSize::Larger => "Larger",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    // Compact whenever the window is narrow
+    #[default]
+    Auto,
+    Comfortable,
+    Compact,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 3] = [Layout::Auto, Layout::Comfortable, Layout::Compact];
+
+    // Below this width the margins of the comfortable layout leave too little room for text
+    const COMPACT_BELOW: f32 = 900.0;
+
+    pub fn is_compact(self, window_width: f32) -> bool {
+        match self {
+            Layout::Auto => window_width < Self::COMPACT_BELOW,
+            Layout::Comfortable => false,
+            Layout::Compact => true,
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Layout::Auto => "Automatic",
+            Layout::Comfortable => "Comfortable",
+            Layout::Compact => "Compact",
+        })
+    }
+}
+
+// When one kind of briefing runs and how far back it looks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Cadence {
+    pub schedule: String,
+    pub window_hours: u32,
+}
+
+impl Default for Cadence {
+    fn default() -> Self {
+        Self {
+            schedule: String::new(),
+            window_hours: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Briefings {
+    pub morning: Cadence,
+    pub end_of_day: Cadence,
+    pub weekly: Cadence,
+}
+
+impl Default for Briefings {
+    fn default() -> Self {
+        Self {
+            // Back to the previous afternoon, so overnight mail is covered
+            morning: Cadence::default(),
+            end_of_day: Cadence {
+                schedule: String::new(),
+                window_hours: 12,
+            },
+            weekly: Cadence {
+                schedule: String::new(),
+                window_hours: 7 * 24,
+            },
+        }
+    }
+}
+
+impl Briefings {
+    pub fn get(&self, kind: history::Kind) -> &Cadence {
+        match kind {
+            history::Kind::Morning => &self.morning,
+            history::Kind::EndOfDay => &self.end_of_day,
+            history::Kind::Weekly => &self.weekly,
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: history::Kind) -> &mut Cadence {
+        match kind {
+            history::Kind::Morning => &mut self.morning,
+            history::Kind::EndOfDay => &mut self.end_of_day,
+            history::Kind::Weekly => &mut self.weekly,
+        }
+    }
+}
+
+// What differs between, say, a work and a personal setup
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub account: Account,
+    pub llm: Llm,
+    // Empty means the built-in prompt for that kind of briefing; the first is the morning one
+    pub prompt_template: String,
+    pub end_of_day_template: String,
+    pub weekly_template: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Profile used when none is picked on the command line
+    #[serde(rename = "profile")]
+    pub active: String,
+    // Briefings report only new developments, resolved items and escalations
+    pub delta: bool,
+    pub theme: ThemeName,
+    // Installed font family for body text, empty for the default
+    pub font: String,
+    pub text_size: TextSize,
+    pub layout: Layout,
+    pub briefings: Briefings,
+    pub profiles: BTreeMap<String, Profile>,
+    // The morning schedule from before briefing kinds, moved into briefings.morning on read
+    #[serde(skip_serializing)]
+    schedule: Option<String>,
+    // The single-account layout from before profiles, moved into a "default" profile on read
+    #[serde(skip_serializing)]
+    account: Option<Account>,
+    #[serde(skip_serializing)]
+    llm: Option<Llm>,
+    #[serde(skip_serializing)]
+    prompt_template: Option<String>,
+}
+
+// Environment variables that override a key of whichever profile is in use
+pub const ENV_OVERRIDES: [&str; 11] = [
+    "IMAP_SERVER",
+    "IMAP_PORT",
+    "IMAP_USERNAME",
+    "IMAP_PASSWORD",
+    "IMAP_FOLDERS",
+    "LLM_PROVIDER",
+    "LLM_MODEL",
+    "GEMINI_MODEL",
+    "GEMINI_API_KEY",
+    "OLLAMA_URL",
+    "BRIEFING_SCHEDULE",
+];
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+pub fn env_overrides() -> Vec<&'static str> {
+    ENV_OVERRIDES
+        .into_iter()
+        .filter(|name| env_var(name).is_some())
+        .collect()
+}
+
+pub fn split_folders(folders: &str) -> Vec<String> {
+    folders
+        .split(',')
+        .map(str::trim)
+        .filter(|folder| !folder.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn secret_problem(name: &str, secret: &Secret) -> Option<String> {
+    if !secret.is_empty() {
+        return None;
+    }
+
+    Some(match secret.kind() {
+        Kind::Plain => format!("{} is required", name),
+        Kind::Command => format!("{} command is required", name),
+        Kind::Vault => format!("{} vault entry name is required", name),
+    })
+}
+
+impl Profile {
+    fn apply_env(&mut self) -> Result<(), String> {
+        let account = &mut self.account;
+        let llm = &mut self.llm;
+
+        if let Some(server) = env_var("IMAP_SERVER") {
+            account.server = server;
+        }
+        if let Some(port) = env_var("IMAP_PORT") {
+            account.port = port
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid IMAP_PORT \"{}\"", port))?;
+        }
+        if let Some(username) = env_var("IMAP_USERNAME") {
+            account.username = username;
+        }
+        if let Some(password) = env_var("IMAP_PASSWORD") {
+            account.password = Secret::Plain(password);
+        }
+        if let Some(folders) = env_var("IMAP_FOLDERS") {
+            account.folders = split_folders(&folders);
+        }
+
+        if let Some(provider) = env_var("LLM_PROVIDER") {
+            llm.provider = match provider.trim().to_lowercase().as_str() {
+                "gemini" => Provider::Gemini,
+                "ollama" => Provider::Ollama,
+                other => return Err(format!("Unknown LLM_PROVIDER \"{}\"", other)),
+            };
+        }
+        // GEMINI_MODEL predates other providers and only means something for Gemini
+        let model = match llm.provider {
+            Provider::Gemini => env_var("LLM_MODEL").or_else(|| env_var("GEMINI_MODEL")),
+            Provider::Ollama => env_var("LLM_MODEL"),
+        };
+        if let Some(model) = model {
+            llm.model = model;
+        }
+        if let Some(api_key) = env_var("GEMINI_API_KEY") {
+            llm.api_key = Secret::Plain(api_key);
+        }
+        if let Some(url) = env_var("OLLAMA_URL") {
+            llm.ollama_url = url;
+        }
+
+        if llm.provider == Provider::Gemini && llm.model.is_empty() {
+            llm.model = ai::DEFAULT_MODEL.to_string();
+        }
+
+        Ok(())
+    }
+
+    pub fn uses_vault(&self) -> bool {
+        let mut secrets = [&self.account.password, &self.llm.api_key].into_iter();
+        secrets.any(|secret| secret.kind() == Kind::Vault)
+    }
+
+    pub fn template_mut(&mut self, kind: history::Kind) -> &mut String {
+        match kind {
+            history::Kind::Morning => &mut self.prompt_template,
+            history::Kind::EndOfDay => &mut self.end_of_day_template,
+            history::Kind::Weekly => &mut self.weekly_template,
+        }
+    }
+
+    pub fn template(&self, kind: history::Kind) -> &str {
+        let template = match kind {
+            history::Kind::Morning => &self.prompt_template,
+            history::Kind::EndOfDay => &self.end_of_day_template,
+            history::Kind::Weekly => &self.weekly_template,
+        };
+
+        if template.trim().is_empty() {
+            prompt::template(kind)
+        } else {
+            template
+        }
+    }
+
+    // Everything that would stop a refresh from working, in the order the form shows the fields
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let account = &self.account;
+
+        if account.server.trim().is_empty() {
+            problems.push(String::from("IMAP server is required"));
+        } else if account.server.contains("://") || account.server.contains(char::is_whitespace) {
+            problems.push(String::from(
+                "IMAP server should be a host name like imap.example.com",
+            ));
+        }
+        if account.port == 0 {
+            problems.push(String::from("IMAP port must be between 1 and 65535"));
+        }
+        if account.username.trim().is_empty() {
+            problems.push(String::from("IMAP username is required"));
+        }
+        problems.extend(secret_problem("IMAP password", &account.password));
+        if account.folders.is_empty() {
+            problems.push(String::from("At least one folder is required"));
+        }
+
+        if self.llm.model.trim().is_empty() {
+            problems.push(String::from("Model is required"));
+        }
+        match self.llm.provider {
+            Provider::Ollama
+                if !self.llm.ollama_url.starts_with("http://")
+                    && !self.llm.ollama_url.starts_with("https://") =>
+            {
+                problems.push(String::from(
+                    "Ollama URL must start with http:// or https://",
+                ));
+            }
+            _ => {}
+        }
+
+        for kind in history::Kind::ALL {
+            let placeholder = prompt::placeholder(kind);
+            if !self.template(kind).contains(placeholder) {
+                problems.push(format!(
+                    "{} prompt must contain {} where the {} go",
+                    kind,
+                    placeholder,
+                    placeholder.trim_matches(['{', '}'])
+                ));
+            }
+        }
+
+        problems
+    }
+
+    // Worth fixing, but a refresh still works: without a model it writes the offline summary
+    pub fn warnings(&self) -> Vec<String> {
+        match self.llm.provider {
+            Provider::Gemini => secret_problem("Gemini API key", &self.llm.api_key)
+                .map(|problem| format!("{}; briefings use the offline summary", problem))
+                .into_iter()
+                .collect(),
+            Provider::Ollama => Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf, String> {
+        let project_dirs = ProjectDirs::from("com", "Apex", "tit-babbler")
+            .ok_or("Could not determine project directory")?;
+        let config_dir = project_dirs.config_dir();
+
+        fs::create_dir_all(config_dir)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+
+        Ok(config_dir.join("config.toml"))
+    }
+
+    // A missing file is not an error, it just means nothing has been saved yet
+    pub fn read() -> Result<Self, String> {
+        let path = Self::path()?;
+
+        let mut config = match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str::<Self>(&content)
+                .map_err(|e| format!("{} is invalid: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+
+        if config.account.is_some() || config.llm.is_some() || config.prompt_template.is_some() {
+            let profile = Profile {
+                account: config.account.take().unwrap_or_default(),
+                llm: config.llm.take().unwrap_or_default(),
+                prompt_template: config.prompt_template.take().unwrap_or_default(),
+                ..Profile::default()
+            };
+            config
+                .profiles
+                .entry(String::from("default"))
+                .or_insert(profile);
+        }
+        if let Some(schedule) = config.schedule.take()
+            && config.briefings.morning.schedule.is_empty()
+        {
+            config.briefings.morning.schedule = schedule;
+        }
+
+        Ok(config)
+    }
+
+    // For callers without a place to show the error
+    pub fn load() -> Self {
+        Self::read().unwrap_or_else(|e| {
+            eprintln!("Ignoring config: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+
+        let path = Self::path()?;
+        // Plain passwords and API keys can live in the file
+        persist::write_private(&path, content.as_bytes())
+    }
+
+    pub fn active_name(&self) -> String {
+        if self.profiles.contains_key(&self.active) {
+            return self.active.clone();
+        }
+
+        self.profiles
+            .keys()
+            .next()
+            .cloned()
+            .unwrap_or_else(|| String::from("default"))
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+
+    // The named profile with environment overrides applied. Without any profiles, the
+    // environment alone describes the setup.
+    pub fn resolve(&self, name: &str) -> Result<Profile, String> {
+        let mut profile = match self.profiles.get(name) {
+            Some(profile) => profile.clone(),
+            None if self.profiles.is_empty() => Profile::default(),
+            None => {
+                return Err(format!(
+                    "No profile named \"{}\" (available: {})",
+                    name,
+                    self.profile_names().join(", ")
+                ));
+            }
+        };
+        profile.apply_env()?;
+
+        Ok(profile)
+    }
+
+    // BRIEFING_SCHEDULE predates the other kinds and overrides the morning schedule
+    pub fn schedules(&self) -> Result<Vec<(history::Kind, Vec<schedule::Rule>)>, String> {
+        history::Kind::ALL
+            .into_iter()
+            .map(|kind| {
+                let spec = match kind {
+                    history::Kind::Morning => env_var("BRIEFING_SCHEDULE"),
+                    _ => None,
+                }
+                .unwrap_or_else(|| self.briefings.get(kind).schedule.clone());
+
+                schedule::parse(&spec)
+                    .map(|rules| (kind, rules))
+                    .map_err(|e| format!("{} schedule: {}", kind, e))
+            })
+            .collect()
+    }
+
+    pub fn problems(&self, name: &str) -> Vec<String> {
+        let mut problems = match self.resolve(name) {
+            Ok(profile) => profile.problems(),
+            Err(e) => vec![e],
+        };
+
+        if let Err(e) = self.schedules() {
+            problems.push(e);
+        }
+        for kind in history::Kind::ALL {
+            if self.briefings.get(kind).window_hours == 0 {
+                problems.push(format!("{} window must be at least one hour", kind));
+            }
+        }
+
+        problems
+    }
+}
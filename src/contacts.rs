@@ -0,0 +1,413 @@
+use crate::fallback;
+use crate::mail::{self, Email};
+use crate::{Message, fonts, secondary_text, section_heading, small_button};
+use iced::widget::{checkbox, column, row, text, text_input};
+use iced::{Element, Length};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::LazyLock;
+
+// What the user knows about the people who write to them, keyed by address. New senders are
+// suggested from their signatures; only entries the user has kept are given to the model.
+
+// Most people the prompt describes at once
+const MAX_RELEVANT: usize = 20;
+
+static ROLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(?:ceo|cto|cfo|coo|founder|co-founder|director|manager|engineer|designer|developer|lead|head of|vp|vice president|president|partner|consultant|analyst|officer|architect|recruiter|producer|editor|assistant|associate|coordinator|specialist|owner|professor|researcher)\b",
+    )
+    .unwrap()
+});
+
+const SIGN_OFFS: [&str; 12] = [
+    "best",
+    "best regards",
+    "regards",
+    "kind regards",
+    "warm regards",
+    "all the best",
+    "thanks",
+    "thank you",
+    "many thanks",
+    "cheers",
+    "sincerely",
+    "talk soon",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Contact {
+    pub address: String,
+    pub name: String,
+    pub role: String,
+    pub organization: String,
+    // How they relate to the user: client, manager, investor, friend...
+    pub relationship: String,
+    pub vip: bool,
+    pub notes: String,
+    // Read from a signature and not yet kept by the user
+    pub suggested: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Edit {
+    Address(String),
+    Name(String),
+    Role(String),
+    Organization(String),
+    Relationship(String),
+    Vip(bool),
+    Notes(String),
+}
+
+impl Contact {
+    pub fn apply(&mut self, edit: Edit) {
+        match edit {
+            Edit::Address(address) => self.address = address,
+            Edit::Name(name) => self.name = name,
+            Edit::Role(role) => self.role = role,
+            Edit::Organization(organization) => self.organization = organization,
+            Edit::Relationship(relationship) => self.relationship = relationship,
+            Edit::Vip(vip) => self.vip = vip,
+            Edit::Notes(notes) => self.notes = notes,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        if self.name.trim().is_empty() {
+            self.address.clone()
+        } else {
+            format!("{} <{}>", self.name.trim(), self.address)
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let position = match (self.role.trim(), self.organization.trim()) {
+            ("", "") => String::new(),
+            (role, "") => role.to_string(),
+            ("", organization) => format!("works at {}", organization),
+            (role, organization) => format!("{} at {}", role, organization),
+        };
+
+        [
+            position.as_str(),
+            self.relationship.trim(),
+            if self.vip { "VIP" } else { "" },
+            self.notes.trim(),
+        ]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("; ")
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Book {
+    contacts: BTreeMap<String, Contact>,
+    // Suggestions the user turned down, so they are not offered again
+    dismissed: BTreeSet<String>,
+}
+
+impl Book {
+    pub fn all(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts.values()
+    }
+
+    // Addresses of the kept contacts marked VIP
+    pub fn vips(&self) -> impl Iterator<Item = String> + '_ {
+        self.contacts
+            .values()
+            .filter(|contact| contact.vip && !contact.suggested)
+            .map(|contact| contact.address.clone())
+    }
+
+    pub fn get(&self, address: &str) -> Option<&Contact> {
+        self.contacts.get(&address.trim().to_lowercase())
+    }
+
+    // Kept entries replace what was there, under a possibly changed address
+    pub fn save(&mut self, previous_address: &str, mut contact: Contact) -> Result<(), String> {
+        contact.address = contact.address.trim().to_lowercase();
+        if !contact.address.contains('@') {
+            return Err(format!("\"{}\" is not an email address", contact.address));
+        }
+
+        self.contacts
+            .remove(&previous_address.trim().to_lowercase());
+        contact.suggested = false;
+        self.dismissed.remove(&contact.address);
+        self.contacts.insert(contact.address.clone(), contact);
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, address: &str) {
+        if let Some(contact) = self.contacts.remove(address)
+            && contact.suggested
+        {
+            self.dismissed.insert(contact.address);
+        }
+    }
+
+    // Adds a suggestion for each new sender whose signature names a role or an organization
+    pub fn suggest(&mut self, emails: &[Email]) -> usize {
+        let mut added = 0;
+
+        for email in emails {
+            let address = fallback::address(&email.from);
+            if !address.contains('@')
+                || self.contacts.contains_key(&address)
+                || self.dismissed.contains(&address)
+            {
+                continue;
+            }
+            let Some((role, organization)) = signature(&email.body) else {
+                continue;
+            };
+
+            self.contacts.insert(
+                address.clone(),
+                Contact {
+                    address,
+                    name: fallback::display_name(&email.from),
+                    role,
+                    organization,
+                    suggested: true,
+                    ..Contact::default()
+                },
+            );
+            added += 1;
+        }
+
+        added
+    }
+
+    // Kept entries for everyone on the emails, senders first
+    pub fn relevant(&self, emails: &[Email]) -> Vec<&Contact> {
+        let addresses = emails
+            .iter()
+            .map(|email| email.from.as_str())
+            .chain(
+                emails
+                    .iter()
+                    .flat_map(|email| [email.to.as_str(), email.cc.as_str()])
+                    .flat_map(|list| list.split(',')),
+            )
+            .map(fallback::address);
+
+        let mut relevant: Vec<&Contact> = Vec::new();
+        for address in addresses {
+            if let Some(contact) = self.contacts.get(&address)
+                && !contact.suggested
+                && !relevant.iter().any(|known| known.address == address)
+            {
+                relevant.push(contact);
+            }
+        }
+        relevant.truncate(MAX_RELEVANT);
+
+        relevant
+    }
+}
+
+fn is_detail(line: &str) -> bool {
+    line.contains('@')
+        || line.contains("http")
+        || line.contains("www.")
+        || line.chars().filter(char::is_ascii_digit).count() >= 5
+}
+
+fn is_sign_off(line: &str) -> bool {
+    let line = line
+        .trim()
+        .trim_end_matches([',', '!', '.'])
+        .trim()
+        .to_lowercase();
+    SIGN_OFFS.contains(&line.as_str())
+}
+
+// Role and organization from the block under a "--" line or a sign-off near the end of the mail.
+// "Head of Design at ScyAI", "Designer | ScyAI" and a role line followed by the company all work.
+fn signature(body: &str) -> Option<(String, String)> {
+    let body = mail::clean_body(body);
+    let lines = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>();
+
+    let tail = lines.len().saturating_sub(12);
+    let start = lines.iter().rposition(|line| *line == "--").or_else(|| {
+        lines[tail..]
+            .iter()
+            .position(|line| is_sign_off(line))
+            .map(|index| tail + index)
+    })?;
+    let block = lines[start + 1..]
+        .iter()
+        .take(6)
+        .copied()
+        .filter(|line| !is_detail(line))
+        .collect::<Vec<&str>>();
+
+    let index = block.iter().position(|line| ROLE.is_match(line))?;
+    let line = block[index];
+
+    let split = [" at ", " | ", " - ", " · ", ", "]
+        .into_iter()
+        .find_map(|separator| line.split_once(separator))
+        .filter(|(_, organization)| !ROLE.is_match(organization));
+    let (role, organization) = match split {
+        Some((role, organization)) => (role, organization),
+        None => (
+            line,
+            block
+                .get(index + 1)
+                .copied()
+                .filter(|next| next.split_whitespace().count() <= 6)
+                .unwrap_or_default(),
+        ),
+    };
+
+    Some((role.trim().to_string(), organization.trim().to_string()))
+}
+
+// Kept contacts, then those suggested from signatures waiting to be kept or dismissed
+pub fn view<'a>(book: &'a Book, form: Option<&'a Contact>) -> Element<'a, Message> {
+    if let Some(contact) = form {
+        let field = |label: &'static str,
+                     placeholder: &'static str,
+                     value: &str,
+                     edit: fn(String) -> Edit| {
+            row![
+                text(label)
+                    .font(fonts::body())
+                    .size(fonts::scaled(12))
+                    .style(secondary_text)
+                    .width(Length::Fixed(110.0)),
+                text_input(placeholder, value)
+                    .font(fonts::body())
+                    .size(fonts::scaled(13))
+                    .on_input(move |value| Message::ContactEdited(edit(value))),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+        };
+
+        return column![
+            section_heading("Contact"),
+            field(
+                "Address",
+                "name@example.com",
+                &contact.address,
+                Edit::Address
+            ),
+            field("Name", "", &contact.name, Edit::Name),
+            field("Role", "Head of Design", &contact.role, Edit::Role),
+            field(
+                "Organization",
+                "",
+                &contact.organization,
+                Edit::Organization
+            ),
+            field(
+                "Relationship",
+                "Client, manager, investor...",
+                &contact.relationship,
+                Edit::Relationship
+            ),
+            field(
+                "Notes",
+                "Anything the briefing should know",
+                &contact.notes,
+                Edit::Notes
+            ),
+            checkbox(contact.vip)
+                .label("VIP: put their mail first")
+                .font(fonts::body())
+                .text_size(fonts::scaled(13))
+                .on_toggle(|vip| Message::ContactEdited(Edit::Vip(vip))),
+            row![
+                small_button("Save", Message::SaveContact),
+                small_button("Cancel", Message::CancelContact),
+            ]
+            .spacing(6),
+        ]
+        .spacing(10)
+        .into();
+    }
+
+    let contact_row = |contact: &Contact, actions: Element<'static, Message>| {
+        let detail = contact.describe();
+        row![
+            column![
+                text(contact.title())
+                    .font(fonts::body())
+                    .size(fonts::scaled(14)),
+                (!detail.is_empty()).then(|| text(detail)
+                    .font(fonts::body())
+                    .size(fonts::scaled(11))
+                    .style(secondary_text)),
+            ]
+            .spacing(2)
+            .width(Length::Fill),
+            actions,
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center)
+    };
+
+    let (suggested, kept): (Vec<&Contact>, Vec<&Contact>) =
+        book.all().partition(|contact| contact.suggested);
+
+    let mut list = column![
+        row![
+            section_heading("People").width(Length::Fill),
+            small_button("Add", Message::EditContact(String::new())),
+        ]
+        .align_y(iced::Alignment::Center)
+    ]
+    .spacing(10);
+    if kept.is_empty() {
+        list = list.push(
+            text("Add the people you work with so briefings know who they are")
+                .font(fonts::body())
+                .size(fonts::scaled(14)),
+        );
+    }
+    for contact in kept {
+        let address = contact.address.clone();
+        list = list.push(contact_row(
+            contact,
+            row![
+                small_button("Edit", Message::EditContact(address.clone())),
+                small_button("Delete", Message::RemoveContact(address)),
+            ]
+            .spacing(6)
+            .into(),
+        ));
+    }
+
+    if !suggested.is_empty() {
+        list = list.push(section_heading("Suggested from signatures"));
+    }
+    for contact in suggested {
+        let address = contact.address.clone();
+        list = list.push(contact_row(
+            contact,
+            row![
+                small_button("Keep", Message::KeepContact(address.clone())),
+                small_button("Edit", Message::EditContact(address.clone())),
+                small_button("Dismiss", Message::RemoveContact(address)),
+            ]
+            .spacing(6)
+            .into(),
+        ));
+    }
+
+    list.into()
+}
@@ -0,0 +1,476 @@
+use crate::archive;
+use crate::history::Briefing;
+use crate::persist;
+use directories::{ProjectDirs, UserDirs};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Markdown,
+    Html,
+    Text,
+    Pdf,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Markdown, Format::Html, Format::Text, Format::Pdf];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Html => "html",
+            Format::Text => "txt",
+            Format::Pdf => "pdf",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Format::Markdown => "Markdown",
+            Format::Html => "HTML",
+            Format::Text => "Text",
+            Format::Pdf => "PDF",
+        }
+    }
+}
+
+struct Source {
+    id: String,
+    subject: Option<String>,
+    from: Option<String>,
+    date: Option<String>,
+}
+
+impl Source {
+    fn describe(&self) -> String {
+        match (&self.subject, &self.from) {
+            (Some(subject), Some(from)) => format!(
+                "{} (from {}, {})",
+                subject,
+                from,
+                self.date.as_deref().unwrap_or("undated")
+            ),
+            _ => self.id.clone(),
+        }
+    }
+}
+
+fn sources(briefing: &Briefing) -> Vec<Source> {
+    briefing
+        .source_email_ids
+        .iter()
+        .map(|id| {
+            let email = archive::load(id);
+            Source {
+                id: id.clone(),
+                subject: email.as_ref().map(|e| e.subject.clone()),
+                from: email.as_ref().map(|e| e.from.clone()),
+                date: email.map(|e| e.date),
+            }
+        })
+        .collect()
+}
+
+fn meta(briefing: &Briefing) -> String {
+    format!(
+        "Covers {} to {} · {}",
+        briefing.window_start.format("%b %-d, %-I:%M %p"),
+        briefing.window_end.format("%b %-d, %-I:%M %p"),
+        briefing.model
+    )
+}
+
+pub fn markdown(briefing: &Briefing) -> String {
+    let mut out = format!("# {}\n\n_{}_\n\n", briefing.title(), meta(briefing));
+
+    for section in briefing.sections() {
+        if let Some(title) = &section.title {
+            out.push_str(&format!("## {}\n\n", title));
+        }
+        out.push_str(&section.text);
+        out.push_str("\n\n");
+    }
+
+    let sources = sources(briefing);
+    if !sources.is_empty() {
+        out.push_str("## Sources\n\n");
+        for (number, source) in sources.iter().enumerate() {
+            out.push_str(&format!(
+                "{}. {} `{}`\n",
+                number + 1,
+                source.describe(),
+                source.id
+            ));
+        }
+    }
+
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn html(briefing: &Briefing) -> String {
+    let mut body = format!(
+        "<h1>{}</h1>\n<p class=\"meta\">{}</p>\n",
+        escape_html(&briefing.title()),
+        escape_html(&meta(briefing))
+    );
+
+    for section in briefing.sections() {
+        body.push_str("<section>\n");
+        if let Some(title) = &section.title {
+            body.push_str(&format!("<h2>{}</h2>\n", escape_html(title)));
+        }
+        body.push_str(&format!("<p>{}</p>\n", escape_html(&section.text)));
+        body.push_str("</section>\n");
+    }
+
+    let sources = sources(briefing);
+    if !sources.is_empty() {
+        body.push_str("<h2>Sources</h2>\n<ol>\n");
+        for source in sources {
+            body.push_str(&format!(
+                "<li>{} <code>{}</code></li>\n",
+                escape_html(&source.describe()),
+                escape_html(&source.id)
+            ));
+        }
+        body.push_str("</ol>\n");
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body {{ font-family: sans-serif; max-width: 720px; margin: 48px auto; line-height: 1.6; color: #222; }} \
+         .meta {{ color: #777; font-size: 0.9em; }} code {{ color: #999; font-size: 0.8em; }}</style>\n\
+         </head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(&briefing.title()),
+        body
+    )
+}
+
+pub fn text(briefing: &Briefing) -> String {
+    let mut out = format!("{}\n{}\n\n", briefing.title(), meta(briefing));
+
+    for section in briefing.sections() {
+        if let Some(title) = &section.title {
+            out.push_str(&format!("{}\n", title.to_uppercase()));
+        }
+        out.push_str(&section.text);
+        out.push_str("\n\n");
+    }
+
+    let sources = sources(briefing);
+    if !sources.is_empty() {
+        out.push_str("Sources\n");
+        for (number, source) in sources.iter().enumerate() {
+            out.push_str(&format!(
+                "  [{}] {} <{}>\n",
+                number + 1,
+                source.describe(),
+                source.id
+            ));
+        }
+    }
+
+    out
+}
+
+pub fn pdf(briefing: &Briefing) -> Vec<u8> {
+    let mut document = pdf::Document::new();
+
+    document.heading(&briefing.title(), 16.0);
+    document.paragraph(&meta(briefing), 9.0);
+
+    for section in briefing.sections() {
+        if let Some(title) = &section.title {
+            document.heading(title, 13.0);
+        }
+        document.paragraph(&section.text, 11.0);
+    }
+
+    let sources = sources(briefing);
+    if !sources.is_empty() {
+        document.heading("Sources", 13.0);
+        for (number, source) in sources.iter().enumerate() {
+            document.paragraph(
+                &format!("[{}] {} <{}>", number + 1, source.describe(), source.id),
+                9.0,
+            );
+        }
+    }
+
+    document.finish()
+}
+
+pub fn render(briefing: &Briefing, format: Format) -> Vec<u8> {
+    match format {
+        Format::Markdown => markdown(briefing).into_bytes(),
+        Format::Html => html(briefing).into_bytes(),
+        Format::Text => text(briefing).into_bytes(),
+        Format::Pdf => pdf(briefing),
+    }
+}
+
+// Downloads folder when there is one, otherwise the app's data directory
+fn export_dir() -> Result<PathBuf, String> {
+    let dir = match UserDirs::new().and_then(|dirs| dirs.download_dir().map(PathBuf::from)) {
+        Some(dir) => dir,
+        None => ProjectDirs::from("com", "Apex", "tit-babbler")
+            .ok_or("Could not determine project directory")?
+            .data_dir()
+            .join("exports"),
+    };
+
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create export directory: {}", e))?;
+
+    Ok(dir)
+}
+
+pub fn export(
+    briefing: &Briefing,
+    format: Format,
+    output: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    let path = match output {
+        Some(path) => path,
+        None => export_dir()?.join(format!(
+            "briefing-{}-{}.{}",
+            briefing.id,
+            briefing.created_at.format("%Y%m%d-%H%M"),
+            format.extension()
+        )),
+    };
+
+    persist::write_atomic(&path, &render(briefing, format))?;
+
+    Ok(path)
+}
+
+// Minimal PDF writer using the standard Helvetica fonts, so no font files are needed
+mod pdf {
+    const PAGE_WIDTH: f32 = 595.0;
+    const PAGE_HEIGHT: f32 = 842.0;
+    const MARGIN: f32 = 56.0;
+
+    // Helvetica advance widths for ASCII 32..=126, in 1/1000 em
+    const WIDTHS: [u16; 95] = [
+        278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556,
+        556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722,
+        722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722,
+        667, 944, 667, 667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556,
+        556, 222, 222, 500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500,
+        500, 334, 260, 334, 584,
+    ];
+
+    // Maps to WinAnsiEncoding; anything outside it becomes '?'
+    fn encode(c: char) -> u8 {
+        match c {
+            ' '..='~' => c as u8,
+            '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        }
+    }
+
+    fn width(byte: u8, size: f32) -> f32 {
+        let units = match byte {
+            32..=126 => WIDTHS[(byte - 32) as usize],
+            _ => 556,
+        };
+
+        units as f32 * size / 1000.0
+    }
+
+    struct Line {
+        bold: bool,
+        size: f32,
+        bytes: Vec<u8>,
+        // Extra space above the line
+        gap: f32,
+    }
+
+    pub struct Document {
+        lines: Vec<Line>,
+    }
+
+    impl Document {
+        pub fn new() -> Self {
+            Self { lines: Vec::new() }
+        }
+
+        fn wrap(&mut self, text: &str, size: f32, bold: bool, gap: f32) {
+            let max_width = PAGE_WIDTH - 2.0 * MARGIN;
+            let mut first = true;
+
+            for source_line in text.lines() {
+                let mut line: Vec<u8> = Vec::new();
+                let mut line_width = 0.0;
+
+                for word in source_line.split_whitespace() {
+                    let word = word.chars().map(encode).collect::<Vec<u8>>();
+                    let word_width = word.iter().map(|b| width(*b, size)).sum::<f32>();
+                    let space = if line.is_empty() {
+                        0.0
+                    } else {
+                        width(b' ', size)
+                    };
+
+                    if !line.is_empty() && line_width + space + word_width > max_width {
+                        self.lines.push(Line {
+                            bold,
+                            size,
+                            bytes: std::mem::take(&mut line),
+                            gap: if first { gap } else { 0.0 },
+                        });
+                        first = false;
+                        line_width = 0.0;
+                    } else if !line.is_empty() {
+                        line.push(b' ');
+                        line_width += space;
+                    }
+
+                    line.extend(word);
+                    line_width += word_width;
+                }
+
+                self.lines.push(Line {
+                    bold,
+                    size,
+                    bytes: line,
+                    gap: if first { gap } else { 0.0 },
+                });
+                first = false;
+            }
+        }
+
+        pub fn heading(&mut self, text: &str, size: f32) {
+            self.wrap(text, size, true, size);
+        }
+
+        pub fn paragraph(&mut self, text: &str, size: f32) {
+            self.wrap(text, size, false, size * 0.8);
+        }
+
+        fn pages(&self) -> Vec<Vec<u8>> {
+            let mut pages = Vec::new();
+            let mut content = Vec::new();
+            let mut y = PAGE_HEIGHT - MARGIN;
+
+            for line in &self.lines {
+                let leading = line.size * 1.4;
+                if y - line.gap - leading < MARGIN && !content.is_empty() {
+                    pages.push(std::mem::take(&mut content));
+                    y = PAGE_HEIGHT - MARGIN;
+                }
+                y -= line.gap + leading;
+
+                let mut escaped = Vec::new();
+                for byte in &line.bytes {
+                    if matches!(byte, b'(' | b')' | b'\\') {
+                        escaped.push(b'\\');
+                    }
+                    escaped.push(*byte);
+                }
+
+                content.extend(
+                    format!(
+                        "BT /{} {} Tf {} {} Td (",
+                        if line.bold { "F2" } else { "F1" },
+                        line.size,
+                        MARGIN,
+                        y
+                    )
+                    .into_bytes(),
+                );
+                content.extend(escaped);
+                content.extend(b") Tj ET\n");
+            }
+
+            pages.push(content);
+            pages
+        }
+
+        pub fn finish(self) -> Vec<u8> {
+            let pages = self.pages();
+            let mut objects: Vec<Vec<u8>> = Vec::new();
+
+            // 1: catalog, 2: page tree, 3 and 4: fonts, then a content stream and page per page
+            let kids = (0..pages.len())
+                .map(|i| format!("{} 0 R", 6 + i * 2))
+                .collect::<Vec<String>>()
+                .join(" ");
+            objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+            objects.push(
+                format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, pages.len()).into_bytes(),
+            );
+            for font in ["Helvetica", "Helvetica-Bold"] {
+                objects.push(
+                    format!(
+                        "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                        font
+                    )
+                    .into_bytes(),
+                );
+            }
+
+            for (i, content) in pages.iter().enumerate() {
+                let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+                stream.extend(content);
+                stream.extend(b"\nendstream");
+                objects.push(stream);
+
+                objects.push(
+                    format!(
+                        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                         /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                        PAGE_WIDTH,
+                        PAGE_HEIGHT,
+                        5 + i * 2
+                    )
+                    .into_bytes(),
+                );
+            }
+
+            let mut out = b"%PDF-1.4\n".to_vec();
+            let mut offsets = Vec::new();
+            for (i, object) in objects.iter().enumerate() {
+                offsets.push(out.len());
+                out.extend(format!("{} 0 obj\n", i + 1).into_bytes());
+                out.extend(object);
+                out.extend(b"\nendobj\n");
+            }
+
+            let xref = out.len();
+            out.extend(
+                format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes(),
+            );
+            for offset in offsets {
+                out.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+            }
+            out.extend(
+                format!(
+                    "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                    objects.len() + 1,
+                    xref
+                )
+                .into_bytes(),
+            );
+
+            out
+        }
+    }
+}
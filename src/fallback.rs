@@ -0,0 +1,517 @@
+use crate::actions::{ActionItem, Change, Found};
+use crate::cite;
+use crate::config::Account;
+use crate::contacts::Book;
+use crate::history::Briefing;
+use crate::mail::Email;
+use chrono::{Datelike, Duration, Local, NaiveDate, Weekday};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::env;
+use std::sync::LazyLock;
+
+// Deterministic, offline summarizer used when the model cannot be reached
+
+const MAX_EMAILS: usize = 8;
+const MAX_SENTENCES: usize = 2;
+
+static TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(?:(?:[01]?\d|2[0-3]):[0-5]\d(?:\s*[ap]\.?m\.?)?|(?:1[0-2]|0?[1-9])\s*[ap]\.?m\.?)",
+    )
+    .unwrap()
+});
+
+static DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(?:today|tonight|tomorrow|monday|tuesday|wednesday|thursday|friday|saturday|sunday|(?:jan|feb|mar|apr|may|jun|jul|aug|sep|sept|oct|nov|dec)[a-z]*\.?\s+\d{1,2}(?:st|nd|rd|th)?|\d{1,2}(?:st|nd|rd|th)?\s+(?:jan|feb|mar|apr|may|jun|jul|aug|sep|sept|oct|nov|dec)[a-z]*|\d{4}-\d{2}-\d{2})\b",
+    )
+    .unwrap()
+});
+
+static MEETING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:meeting|call|sync|standup|stand-up|interview|invite|invitation|zoom|google meet|teams|agenda|catch up|1:1|webinar)\b")
+        .unwrap()
+});
+
+static SENTENCE_END: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[.!?]+\s+|\n+").unwrap());
+
+const URGENT_KEYWORDS: [&str; 6] = [
+    "urgent",
+    "asap",
+    "immediately",
+    "blocker",
+    "blocked",
+    "critical",
+];
+const DEADLINE_KEYWORDS: [&str; 5] = ["deadline", "due", "eod", "end of day", "by tomorrow"];
+const REQUEST_KEYWORDS: [&str; 5] = ["can you", "could you", "please", "let me know", "need you"];
+// Whole words only, so "due" does not match "residue"
+fn keywords(words: &[&str]) -> Regex {
+    let words = words
+        .iter()
+        .map(|word| regex::escape(word))
+        .collect::<Vec<String>>();
+
+    Regex::new(&format!(r"(?i)\b(?:{})\b", words.join("|"))).unwrap()
+}
+
+static URGENT: LazyLock<Regex> = LazyLock::new(|| keywords(&URGENT_KEYWORDS));
+static DEADLINE: LazyLock<Regex> = LazyLock::new(|| keywords(&DEADLINE_KEYWORDS));
+static REQUEST: LazyLock<Regex> = LazyLock::new(|| keywords(&REQUEST_KEYWORDS));
+
+const AUTOMATED_SENDERS: [&str; 8] = [
+    "noreply",
+    "no-reply",
+    "donotreply",
+    "notification",
+    "newsletter",
+    "mailer-daemon",
+    "news@",
+    "updates@",
+];
+
+pub fn display_name(from: &str) -> String {
+    match from.split_once('<') {
+        Some((name, _)) if !name.trim().is_empty() => name.trim().trim_matches('"').to_string(),
+        _ => address(from),
+    }
+}
+
+pub fn address(from: &str) -> String {
+    match from.split_once('<') {
+        Some((_, rest)) => rest.trim_end_matches('>').trim().to_lowercase(),
+        None => from.trim().to_lowercase(),
+    }
+}
+
+pub fn user_address(account: &Account) -> Option<String> {
+    env::var("USER_EMAIL")
+        .ok()
+        .or_else(|| Some(account.username.clone()))
+        .filter(|address| address.contains('@'))
+        .map(|address| address.to_lowercase())
+}
+
+// A VIP is an address, or a whole domain written as example.com or @example.com
+fn is_vip(sender: &str, vip: &str) -> bool {
+    if vip.contains('@') && !vip.starts_with('@') {
+        return sender == vip;
+    }
+
+    sender
+        .rsplit_once('@')
+        .is_some_and(|(_, domain)| domain == vip.trim_start_matches('@'))
+}
+
+// VIP_SENDERS may name whole domains; contacts marked VIP add their addresses
+fn vip_senders(contacts: &Book) -> Vec<String> {
+    env::var("VIP_SENDERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|sender| sender.trim().to_lowercase())
+        .filter(|sender| !sender.is_empty())
+        .chain(contacts.vips())
+        .collect()
+}
+
+fn keyword_score(text: &str) -> i32 {
+    let text = text.to_lowercase();
+    // Each keyword counts once however often it appears
+    let hits = |keywords: &Regex| {
+        keywords
+            .find_iter(&text)
+            .map(|found| found.as_str())
+            .collect::<HashSet<&str>>()
+            .len() as i32
+    };
+
+    hits(&URGENT) * 3
+        + hits(&DEADLINE) * 2
+        + hits(&REQUEST)
+        + MEETING.find_iter(&text).count().min(1) as i32
+}
+
+fn email_score(email: &Email, user: Option<&str>, vips: &[String]) -> i32 {
+    let sender = address(&email.from);
+    let mut score = 0;
+
+    if vips.iter().any(|vip| is_vip(&sender, vip)) {
+        score += 3;
+    }
+    if AUTOMATED_SENDERS.iter().any(|a| sender.contains(a)) {
+        score -= 3;
+    }
+    if let Some(user) = user {
+        if email.to.to_lowercase().contains(user) {
+            score += 2;
+        } else if email.cc.to_lowercase().contains(user) {
+            score += 1;
+        }
+    }
+
+    score + keyword_score(&email.subject) * 2 + keyword_score(&email.body)
+}
+
+// Drops quoted replies and signatures so sentences come from the new text only
+fn fresh_text(body: &str) -> String {
+    let mut lines = Vec::new();
+
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if trimmed == "--" || (trimmed.starts_with("On ") && trimmed.ends_with("wrote:")) {
+            break;
+        }
+        if !trimmed.starts_with('>') {
+            lines.push(trimmed);
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn sentences(body: &str) -> Vec<String> {
+    SENTENCE_END
+        .split(&fresh_text(body))
+        .map(|s| s.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|s| s.len() > 12)
+        .collect()
+}
+
+fn shorten(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+
+    let cut = text.chars().take(limit).collect::<String>();
+    match cut.rsplit_once(' ') {
+        Some((head, _)) => format!("{}...", head),
+        None => format!("{}...", cut),
+    }
+}
+
+fn key_sentences(email: &Email) -> Vec<String> {
+    let mut scored = sentences(&email.body)
+        .into_iter()
+        .enumerate()
+        .map(|(position, sentence)| {
+            let mut score = keyword_score(&sentence) * 2;
+            if sentence.contains('?') {
+                score += 1;
+            }
+            if TIME.is_match(&sentence) || DATE.is_match(&sentence) {
+                score += 1;
+            }
+            // Earlier sentences usually carry the point of the mail
+            (score * 10 - position as i32, position, sentence)
+        })
+        .collect::<Vec<(i32, usize, String)>>();
+
+    scored.sort_by_key(|(score, _, _)| Reverse(*score));
+    scored.truncate(MAX_SENTENCES);
+    scored.sort_by_key(|(_, position, _)| *position);
+
+    scored
+        .into_iter()
+        .map(|(_, _, sentence)| shorten(&sentence, 200))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Meeting {
+    pub title: String,
+    pub when: String,
+    pub organizer: String,
+    // The email that mentions it
+    pub email_id: String,
+}
+
+pub fn find_meetings(emails: &[Email]) -> Vec<Meeting> {
+    let mut meetings = Vec::new();
+
+    for email in emails {
+        let candidates = std::iter::once(email.subject.clone()).chain(sentences(&email.body));
+
+        for candidate in candidates {
+            if !MEETING.is_match(&candidate) {
+                continue;
+            }
+
+            let when = DATE
+                .find_iter(&candidate)
+                .chain(TIME.find_iter(&candidate))
+                .map(|m| m.as_str().to_string())
+                .collect::<Vec<String>>();
+            if when.is_empty() {
+                continue;
+            }
+
+            meetings.push(Meeting {
+                title: shorten(&email.subject, 80),
+                when: when.join(" "),
+                organizer: display_name(&email.from),
+                email_id: email.id.clone(),
+            });
+            break;
+        }
+    }
+
+    meetings
+}
+
+// Days named relative to when the mail is read; anything vaguer is left undated
+fn due_date(sentence: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let found = DATE.find(sentence)?.as_str().to_lowercase();
+
+    match found.as_str() {
+        "today" | "tonight" => Some(today),
+        "tomorrow" => today.succ_opt(),
+        day => match day.parse::<Weekday>() {
+            Ok(weekday) => (1..=7)
+                .map(|ahead| today + Duration::days(ahead))
+                .find(|date| date.weekday() == weekday),
+            Err(_) => NaiveDate::parse_from_str(day, "%Y-%m-%d").ok(),
+        },
+    }
+}
+
+// Questions and requests addressed to the user, for when the model cannot list them
+pub fn action_items(emails: &[Email], account: &Account, contacts: &Book) -> Vec<Found> {
+    let user = user_address(account);
+    let vips = vip_senders(contacts);
+    let today = Local::now().date_naive();
+
+    emails
+        .iter()
+        .filter(|email| email_score(email, user.as_deref(), &vips) >= 0)
+        .flat_map(|email| {
+            sentences(&email.body)
+                .into_iter()
+                .filter(|sentence| sentence.contains('?') || REQUEST.is_match(sentence))
+                .take(1)
+                .map(|sentence| Found {
+                    task: shorten(&sentence, 160),
+                    person: display_name(&email.from),
+                    email_id: Some(email.id.clone()),
+                    due: due_date(&sentence, today),
+                    change: Change::Unchanged,
+                })
+        })
+        .collect()
+}
+
+pub fn summarize(emails: &[Email], account: &Account, contacts: &Book) -> String {
+    let user = user_address(account);
+    let vips = vip_senders(contacts);
+
+    let mut ranked = emails
+        .iter()
+        .map(|email| (email_score(email, user.as_deref(), &vips), email))
+        .collect::<Vec<(i32, &Email)>>();
+    ranked.sort_by_key(|(score, _)| Reverse(*score));
+
+    let skipped = ranked.iter().filter(|(score, _)| *score < 0).count();
+    let important = ranked
+        .iter()
+        .filter(|(score, _)| *score >= 0)
+        .take(MAX_EMAILS)
+        .map(|(_, email)| *email)
+        .collect::<Vec<&Email>>();
+
+    let mut paragraphs = vec![format!(
+        "Good day. The assistant is unavailable, so this is an offline summary of the {} most relevant of {} emails.",
+        important.len(),
+        emails.len()
+    )];
+
+    let meetings = find_meetings(emails);
+    if !meetings.is_empty() {
+        let listed = meetings
+            .iter()
+            .map(|m| format!("{} ({}, from {})", m.title, m.when, m.organizer))
+            .collect::<Vec<String>>()
+            .join("; ");
+        paragraphs.push(format!("Meetings mentioned: {}.", listed));
+    }
+
+    for email in important {
+        let sentences = key_sentences(email);
+        let detail = if sentences.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", sentences.join(" "))
+        };
+
+        paragraphs.push(format!(
+            "{} wrote about \"{}\".{} [#{}]",
+            display_name(&email.from),
+            shorten(&email.subject, 80),
+            detail,
+            cite::reference(&email.id)
+        ));
+    }
+
+    if skipped > 0 {
+        paragraphs.push(format!(
+            "{} automated or low-priority emails were left out.",
+            skipped
+        ));
+    }
+
+    paragraphs.join("\n\n")
+}
+
+// The week without the model: each briefing's opening lines, then what got done and what is left
+pub fn weekly(briefings: &[Briefing], open: &[ActionItem], done: &[ActionItem]) -> String {
+    let mut paragraphs = vec![format!(
+        "Good day. The assistant is unavailable, so this is an offline digest of {} briefings.",
+        briefings.len()
+    )];
+
+    for briefing in briefings {
+        let opening = cite::strip(&briefing.text)
+            .split("\n\n")
+            .map(str::trim)
+            .filter(|paragraph| !paragraph.is_empty())
+            .find(|paragraph| !paragraph.starts_with("Good "))
+            .map(|paragraph| shorten(paragraph, 300))
+            .unwrap_or_default();
+
+        paragraphs.push(format!(
+            "{}: {}",
+            briefing.created_at.format("%A"),
+            if opening.is_empty() {
+                String::from("Nothing of note.")
+            } else {
+                opening
+            }
+        ));
+    }
+
+    let listed = |items: &[ActionItem]| {
+        items
+            .iter()
+            .map(|item| item.task.trim_end_matches('.').to_string())
+            .collect::<Vec<String>>()
+            .join("; ")
+    };
+    if !done.is_empty() {
+        paragraphs.push(format!("Done this week: {}.", listed(done)));
+    }
+    if !open.is_empty() {
+        paragraphs.push(format!("Still open: {}.", listed(open)));
+    }
+
+    paragraphs.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(from: &str, to: &str, subject: &str, body: &str) -> Email {
+        Email {
+            id: String::from(subject),
+            subject: String::from(subject),
+            body: String::from(body),
+            from: String::from(from),
+            to: String::from(to),
+            cc: String::new(),
+            date: String::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    fn vips(list: &[&str]) -> Vec<String> {
+        list.iter().map(|vip| vip.to_string()).collect()
+    }
+
+    #[test]
+    fn vip_matches_whole_addresses_and_domains() {
+        assert!(is_vip("bob@x.com", "bob@x.com"));
+        assert!(!is_vip("jimbob@x.com", "bob@x.com"));
+        assert!(is_vip("jim@x.com", "x.com"));
+        assert!(is_vip("jim@x.com", "@x.com"));
+        assert!(!is_vip("jim@fax.com", "x.com"));
+        assert!(!is_vip("jim@x.com.evil", "x.com"));
+    }
+
+    #[test]
+    fn keywords_match_whole_words_only() {
+        assert_eq!(keyword_score("Some residue was left"), 0);
+        assert_eq!(keyword_score("The draft is due"), 2);
+        assert_eq!(keyword_score("Urgent, urgent: it is due"), 5);
+        assert_eq!(keyword_score("We are pleased to announce"), 0);
+        assert_eq!(keyword_score("Please review"), 1);
+    }
+
+    #[test]
+    fn email_score_ranks_people_over_automated_mail() {
+        let user = Some("me@example.com");
+        let vips = vips(&["boss@example.com"]);
+
+        let boss = email("Boss <boss@example.com>", "me@example.com", "Hi", "Lunch?");
+        let lookalike = email(
+            "Jim <jimboss@example.com>",
+            "me@example.com",
+            "Hi",
+            "Lunch?",
+        );
+        let urgent = email(
+            "Ada <ada@example.com>",
+            "team@example.com",
+            "Urgent: deploy blocked",
+            "Can you look before the deadline?",
+        );
+        let newsletter = email(
+            "News <noreply@shop.com>",
+            "me@example.com",
+            "Weekly deals",
+            "Residue-free cleaning.",
+        );
+
+        let score = |email: &Email| email_score(email, user, &vips);
+        assert_eq!(score(&boss), score(&lookalike) + 3);
+        assert!(score(&urgent) > score(&boss));
+        assert!(score(&newsletter) < 0);
+    }
+
+    #[test]
+    fn due_date_reads_relative_and_iso_days() {
+        // A Wednesday
+        let today = NaiveDate::from_ymd_opt(2025, 1, 8).unwrap();
+        let due = |sentence: &str| due_date(sentence, today);
+
+        assert_eq!(due("Can you send it today?"), Some(today));
+        assert_eq!(due("Please reply by tomorrow"), today.succ_opt());
+        assert_eq!(
+            due("Could you review it by Friday?"),
+            NaiveDate::from_ymd_opt(2025, 1, 10)
+        );
+        // The same weekday means next week, not today
+        assert_eq!(
+            due("Let me know by Wednesday"),
+            NaiveDate::from_ymd_opt(2025, 1, 15)
+        );
+        assert_eq!(
+            due("Due 2025-02-01, please"),
+            NaiveDate::from_ymd_opt(2025, 2, 1)
+        );
+        // Named but too vague to place in a year
+        assert_eq!(due("Please send it by March 3rd"), None);
+        assert_eq!(due("Please send it soon"), None);
+        assert_eq!(due("Sundays are for mondayitis"), None);
+    }
+
+    #[test]
+    fn date_and_time_patterns_need_word_boundaries() {
+        assert!(DATE.is_match("See you on Jan 5th"));
+        assert!(DATE.is_match("Back on 12 March"));
+        assert!(!DATE.is_match("The todays list"));
+        assert!(TIME.is_match("Call at 3pm"));
+        assert!(TIME.is_match("Starts 14:30"));
+        assert!(!TIME.is_match("Version 25:99"));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::config::Config;
+use font_kit::source::SystemSource;
+use iced::Font;
+use std::sync::{LazyLock, RwLock};
+
+// The face and size every view draws body text with. Set from the config on startup and while
+// settings are edited, so a new pick previews before it is saved.
+
+// Used when nothing is picked and it is installed
+const PREFERRED: &str = "Pretendard Variable";
+// Shipped with the app for when the picked family is missing
+pub const FALLBACK: &str = "Inter";
+pub const BUNDLED: &[u8] = include_bytes!("../assets/fonts/Inter-Regular.ttf");
+
+static INSTALLED: LazyLock<Vec<String>> = LazyLock::new(|| {
+    let mut families = SystemSource::new().all_families().unwrap_or_default();
+    families.sort_by_key(|family| family.to_lowercase());
+    families.dedup();
+    families
+});
+
+static BODY: RwLock<(Font, f32)> = RwLock::new((Font::with_name(FALLBACK), 16.0));
+
+pub fn installed() -> &'static [String] {
+    &INSTALLED
+}
+
+// Names borrowed from the installed list live as long as the app, as iced needs
+fn family(name: &str) -> &'static str {
+    let name = if name.is_empty() { PREFERRED } else { name };
+
+    INSTALLED
+        .iter()
+        .find(|family| family.eq_ignore_ascii_case(name))
+        .map_or(FALLBACK, String::as_str)
+}
+
+pub fn apply(config: &Config) {
+    let body = (
+        Font::with_name(family(&config.font)),
+        config.text_size.points(),
+    );
+    if let Ok(mut current) = BODY.write() {
+        *current = body;
+    }
+}
+
+pub fn body() -> Font {
+    BODY.read().map_or(Font::with_name(FALLBACK), |body| body.0)
+}
+
+// Sizes in the views are written for 16 point body text and grow or shrink with it
+pub fn scaled(size: u16) -> f32 {
+    let points = BODY.read().map_or(16.0, |body| body.1);
+    (f32::from(size) * points / 16.0).round()
+}
@@ -0,0 +1,282 @@
+use crate::actions::Found;
+use crate::cite;
+use crate::fallback::Meeting;
+use crate::persist;
+use crate::projects::Update;
+use crate::usage::UsageRecord;
+use chrono::{DateTime, Local, NaiveDate};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Default,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    // Overnight mail and the day ahead; every briefing from before kinds existed is one
+    #[default]
+    Morning,
+    // What came in during the day and what is still open
+    EndOfDay,
+    // Built from the week's stored briefings and action items rather than from mail
+    Weekly,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Morning, Kind::EndOfDay, Kind::Weekly];
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Morning => "Morning",
+            Kind::EndOfDay => "End of day",
+            Kind::Weekly => "Weekly",
+        })
+    }
+}
+
+// Which briefings the history is browsed through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Filter {
+    #[default]
+    All,
+    Only(Kind),
+}
+
+impl Filter {
+    pub const ALL: [Filter; 4] = [
+        Filter::All,
+        Filter::Only(Kind::Morning),
+        Filter::Only(Kind::EndOfDay),
+        Filter::Only(Kind::Weekly),
+    ];
+
+    pub fn matches(self, briefing: &Briefing) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Only(kind) => briefing.kind == kind,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::All => f.write_str("All briefings"),
+            Filter::Only(kind) => kind.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Briefing {
+    // Assigned when the briefing is stored, 0 until then
+    pub id: u64,
+    #[serde(default)]
+    pub kind: Kind,
+    pub created_at: DateTime<Local>,
+    pub window_start: DateTime<Local>,
+    pub window_end: DateTime<Local>,
+    pub source_email_ids: Vec<String>,
+    pub model: String,
+    pub usage: Option<UsageRecord>,
+    pub text: String,
+    pub cached: bool,
+    // Set to the model error when the offline summarizer produced this briefing
+    pub degraded: Option<String>,
+    // Questions and requests found in this briefing, merged into the tracker when stored
+    #[serde(default)]
+    pub action_items: Vec<Found>,
+    // Projects this briefing moved on, merged into the project memory when stored
+    #[serde(default)]
+    pub projects: Vec<Update>,
+    // Meetings the model found in the mail; none for offline and older briefings, whose
+    // meetings are looked for in the mail instead
+    #[serde(default)]
+    pub meetings: Option<Vec<Meeting>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub title: Option<String>,
+    pub text: String,
+}
+
+impl Briefing {
+    pub fn label(&self) -> String {
+        self.created_at.format("%b %-d, %-I:%M %p").to_string()
+    }
+
+    pub fn title(&self) -> String {
+        format!("{} briefing · {}", self.kind, self.label())
+    }
+
+    pub fn paragraphs(&self) -> Vec<&str> {
+        self.text
+            .split("\n\n")
+            .map(str::trim)
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect()
+    }
+
+    // No mail arrived in the window, so there is nothing to store
+    pub fn is_empty(&self) -> bool {
+        self.source_email_ids.is_empty() && self.text.trim().is_empty()
+    }
+
+    // The briefing is prose, so each paragraph is its own section
+    pub fn sections(&self) -> Vec<Section> {
+        self.paragraphs()
+            .into_iter()
+            .map(|paragraph| Section {
+                title: None,
+                text: cite::numbered(self, paragraph),
+            })
+            .collect()
+    }
+}
+
+// One file per briefing so a bad write can only ever lose a single entry
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    briefings: Vec<Briefing>,
+}
+
+impl History {
+    fn history_dir() -> Result<PathBuf, String> {
+        let project_dirs = ProjectDirs::from("com", "Apex", "tit-babbler")
+            .ok_or("Could not determine project directory")?;
+        let history_dir = project_dirs.data_dir().join("history");
+
+        fs::create_dir_all(&history_dir)
+            .map_err(|e| format!("Failed to create history directory: {}", e))?;
+
+        Ok(history_dir)
+    }
+
+    pub fn load() -> Self {
+        let mut briefings = Vec::new();
+
+        let entries = match Self::history_dir()
+            .and_then(|dir| fs::read_dir(dir).map_err(|e| format!("Failed to read history: {}", e)))
+        {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Self::default();
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            match fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    serde_json::from_str::<Briefing>(&content).map_err(|e| e.to_string())
+                }) {
+                Ok(briefing) => briefings.push(briefing),
+                Err(e) => eprintln!("Skipping unreadable briefing {}: {}", path.display(), e),
+            }
+        }
+
+        briefings.sort_by_key(|b| b.id);
+
+        Self { briefings }
+    }
+
+    pub fn push(&mut self, mut briefing: Briefing) -> Result<u64, String> {
+        briefing.id = self.briefings.last().map_or(1, |b| b.id + 1);
+
+        let path = Self::history_dir()?.join(format!("{}.json", briefing.id));
+        let json = serde_json::to_string_pretty(&briefing)
+            .map_err(|e| format!("Failed to serialize briefing: {}", e))?;
+        persist::write_atomic(&path, json.as_bytes())?;
+
+        let id = briefing.id;
+        self.briefings.push(briefing);
+
+        Ok(id)
+    }
+
+    pub fn all(&self) -> &[Briefing] {
+        &self.briefings
+    }
+
+    pub fn latest(&self, filter: Filter) -> Option<&Briefing> {
+        self.briefings.iter().rev().find(|b| filter.matches(b))
+    }
+
+    // The stored briefing a cached one repeats, none if it has been deleted since
+    pub fn original(&self, cached: &Briefing) -> Option<u64> {
+        self.briefings
+            .iter()
+            .rev()
+            .find(|b| {
+                b.kind == cached.kind
+                    && b.source_email_ids == cached.source_email_ids
+                    && b.text == cached.text
+            })
+            .map(|b| b.id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Briefing> {
+        self.position(id).map(|i| &self.briefings[i])
+    }
+
+    pub fn position(&self, id: u64) -> Option<usize> {
+        self.briefings.binary_search_by_key(&id, |b| b.id).ok()
+    }
+
+    pub fn older(&self, id: u64, filter: Filter) -> Option<&Briefing> {
+        let position = self.position(id)?;
+        self.briefings[..position]
+            .iter()
+            .rev()
+            .find(|b| filter.matches(b))
+    }
+
+    pub fn newer(&self, id: u64, filter: Filter) -> Option<&Briefing> {
+        let position = self.position(id)?;
+        self.briefings[position + 1..]
+            .iter()
+            .find(|b| filter.matches(b))
+    }
+
+    // Where the briefing falls among those the filter lets through, and how many there are
+    pub fn rank(&self, id: u64, filter: Filter) -> Option<(usize, usize)> {
+        let matching = self
+            .briefings
+            .iter()
+            .filter(|b| filter.matches(b))
+            .collect::<Vec<&Briefing>>();
+        let rank = matching.iter().position(|b| b.id == id)?;
+
+        Some((rank, matching.len()))
+    }
+
+    // The last briefing written on the given day
+    pub fn on_date(&self, date: NaiveDate, filter: Filter) -> Option<&Briefing> {
+        self.briefings
+            .iter()
+            .rev()
+            .find(|b| b.created_at.date_naive() == date && filter.matches(b))
+    }
+}
@@ -0,0 +1,95 @@
+use crate::config::Profile;
+use crate::history::Kind;
+use crate::mute::Rules;
+use crate::prompt::Context;
+use crate::{Message, refresh_inbox};
+use futures::channel::mpsc::{self, UnboundedSender};
+use iced::Task;
+use iced::task::Handle;
+use std::fmt;
+
+// A running refresh. There is at most one, it can be cancelled, and it reports each stage so the
+// window can say what it is waiting on.
+
+#[derive(Debug, Clone)]
+pub enum Stage {
+    Connecting(String),
+    LoggedIn,
+    Searching(String),
+    Fetching { done: usize, total: usize },
+    Parsing(usize),
+    Summarizing(usize),
+    Digesting(usize),
+    Streaming(usize),
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Connecting(server) => write!(f, "Connecting to {}...", server),
+            Stage::LoggedIn => f.write_str("Logged in"),
+            Stage::Searching(folder) => write!(f, "Searching {}...", folder),
+            Stage::Fetching { done, total } => write!(f, "Fetching {} of {}...", done, total),
+            Stage::Parsing(count) => write!(f, "Parsing {} emails...", count),
+            Stage::Summarizing(count) => write!(f, "Summarizing {} emails...", count),
+            Stage::Digesting(count) => write!(f, "Digesting {} briefings...", count),
+            Stage::Streaming(characters) => {
+                write!(f, "Writing the briefing ({} characters)...", characters)
+            }
+        }
+    }
+}
+
+// Where a refresh sends its stages; the command line passes one that goes nowhere
+#[derive(Debug, Clone, Default)]
+pub struct Progress(Option<UnboundedSender<Stage>>);
+
+impl Progress {
+    // A closed channel only means nobody is watching any more
+    pub fn report(&self, stage: Stage) {
+        if let Some(sender) = &self.0 {
+            let _ = sender.unbounded_send(stage);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    handle: Handle,
+}
+
+impl Job {
+    pub fn start(
+        profile: Profile,
+        kind: Kind,
+        window_hours: u32,
+        force: bool,
+        context: Context,
+        muted: Rules,
+    ) -> (Self, Task<Message>) {
+        let (sender, receiver) = mpsc::unbounded();
+
+        // The stage stream ends when the refresh drops its sender
+        let refresh = Task::perform(
+            refresh_inbox(
+                profile,
+                kind,
+                window_hours,
+                force,
+                context,
+                muted,
+                Progress(Some(sender)),
+            ),
+            |result| Message::SummaryGenerated(result.map(Box::new)),
+        );
+        let stages = Task::run(receiver, Message::RefreshProgress);
+
+        let (task, handle) = Task::batch([stages, refresh]).abortable();
+
+        (Self { handle }, task)
+    }
+
+    pub fn cancel(self) {
+        self.handle.abort();
+    }
+}
@@ -1,8 +1,14 @@
-use chrono::{Duration, Local};
+use crate::cite;
+use crate::config::Account;
+use crate::job::{Progress, Stage};
+use chrono::{DateTime, Local, NaiveDate};
 use futures::stream::StreamExt;
+use mailparse::DispositionType;
+use regex::Regex;
 use rustls::pki_types::ServerName;
-use std::env;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, LazyLock};
 use tokio::net::TcpStream;
 use tokio_rustls::TlsConnector;
 use tokio_rustls::client::TlsStream;
@@ -11,12 +17,51 @@ use tokio_rustls::rustls::{ClientConfig, RootCertStore};
 // Helper
 type Tls = TlsStream<TcpStream>;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Email {
+    pub id: String,
     pub subject: String,
     pub body: String,
     pub from: String,
+    pub to: String,
+    pub cc: String,
+    pub date: String,
+    // Absent from emails archived before attachments were recorded
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub mime_type: String,
+    pub size: usize,
+}
+
+impl Attachment {
+    pub fn describe(&self) -> String {
+        let size = if self.size >= 1024 * 1024 {
+            format!("{:.1} MB", self.size as f64 / (1024.0 * 1024.0))
+        } else {
+            format!("{} KB", self.size.div_ceil(1024))
+        };
+
+        format!("{} ({}, {})", self.name, self.mime_type, size)
+    }
+}
+
+// When the email was sent, per its Date header
+pub fn received_at(email: &Email) -> Option<DateTime<Local>> {
+    let timestamp = mailparse::dateparse(&email.date).ok()?;
+    DateTime::from_timestamp(timestamp, 0).map(|date| date.with_timezone(&Local))
+}
+
+static HTML_DROPPED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<(style|script|head)\b.*?</(style|script|head)>").unwrap());
+static HTML_BREAK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<br\s*/?>|</(p|div|tr|li|h[1-6])>").unwrap());
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+
 fn get_header_value(parsed: &mailparse::ParsedMail, name: &str) -> Option<String> {
     parsed
         .headers
@@ -25,6 +70,33 @@ fn get_header_value(parsed: &mailparse::ParsedMail, name: &str) -> Option<String
         .map(|h| h.get_value())
 }
 
+// Message-ID when present, otherwise a digest of the headers that identify the mail
+fn email_id(parsed: &mailparse::ParsedMail) -> String {
+    if let Some(message_id) = get_header_value(parsed, "Message-ID") {
+        let message_id = message_id.trim().trim_matches(['<', '>']);
+        if !message_id.is_empty() {
+            return message_id.to_string();
+        }
+    }
+
+    let mut hasher = Sha256::new();
+    for name in ["From", "Date", "Subject"] {
+        hasher.update(
+            get_header_value(parsed, name)
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+        hasher.update([0]);
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .take(16)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
 fn extract_body(parsed: &mailparse::ParsedMail) -> Result<String, mailparse::MailParseError> {
     // If this part is text/plain or text/html, use it directly
     if parsed.ctype.mimetype.starts_with("text/") {
@@ -49,13 +121,78 @@ fn extract_body(parsed: &mailparse::ParsedMail) -> Result<String, mailparse::Mai
     parsed.get_body()
 }
 
-pub async fn fetch_emails() -> Result<Vec<Email>, String> {
-    let imap_server = env::var("IMAP_SERVER").expect("IMAP_SERVER not set");
-    let imap_username = env::var("IMAP_USERNAME").expect("IMAP_USERNAME not set");
-    let imap_password = env::var("IMAP_PASSWORD").expect("IMAP_PASSWORD not set");
+// Parts marked as attachments, or carrying a file name, anywhere in the message
+fn attachments(parsed: &mailparse::ParsedMail) -> Vec<Attachment> {
+    parsed
+        .parts()
+        .filter_map(|part| {
+            let disposition = part.get_content_disposition();
+            let name = disposition
+                .params
+                .get("filename")
+                .or_else(|| part.ctype.params.get("name"))
+                .cloned();
+            if disposition.disposition != DispositionType::Attachment && name.is_none() {
+                return None;
+            }
+
+            Some(Attachment {
+                name: name.unwrap_or_else(|| String::from("(unnamed)")),
+                mime_type: part.ctype.mimetype.clone(),
+                size: part.get_body_raw().map(|body| body.len()).unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+fn looks_like_html(body: &str) -> bool {
+    let lower = body.to_lowercase();
+    ["<html", "<body", "<div", "<p>", "<br", "<table"]
+        .iter()
+        .any(|tag| lower.contains(tag))
+}
+
+// The body as a reader wants it: tags stripped from HTML-only mail and quoted replies dropped
+pub fn clean_body(body: &str) -> String {
+    let text = if looks_like_html(body) {
+        let text = HTML_DROPPED.replace_all(body, "");
+        let text = HTML_BREAK.replace_all(&text, "\n");
+        HTML_TAG
+            .replace_all(&text, "")
+            .replace("&nbsp;", " ")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&")
+    } else {
+        body.replace("\r\n", "\n")
+    };
+
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("On ") && trimmed.ends_with("wrote:") {
+            break;
+        }
+        // At most one blank line in a row
+        if trimmed.starts_with('>') || (trimmed.is_empty() && lines.last() == Some(&"")) {
+            continue;
+        }
+        lines.push(line.trim_end());
+    }
+
+    lines.join("\n").trim().to_string()
+}
+
+async fn connect(account: &Account) -> Result<async_imap::Session<Tls>, String> {
+    if account.server.is_empty() {
+        return Err(String::from("IMAP server not set"));
+    }
+    let password = account.password.reveal()?;
 
     // Establishing a connection
-    let tcp = TcpStream::connect((imap_server.as_str(), 993u16))
+    let tcp = TcpStream::connect((account.server.as_str(), account.port))
         .await
         .map_err(|e| format!("Failed to connect to IMAP server: {}", e))?;
 
@@ -74,7 +211,7 @@ pub async fn fetch_emails() -> Result<Vec<Email>, String> {
 
     // Converting server name
     let domain =
-        ServerName::try_from(imap_server.to_owned()).map_err(|_| "Invalid DNS name".to_string())?;
+        ServerName::try_from(account.server.clone()).map_err(|_| "Invalid DNS name".to_string())?;
 
     let tls = connector
         .connect(domain, tcp)
@@ -82,29 +219,73 @@ pub async fn fetch_emails() -> Result<Vec<Email>, String> {
         .map_err(|e| format!("Failed to establish TLS connection: {}", e))?;
 
     // Wrap stream and login
-    let mut imap: async_imap::Session<Tls> = async_imap::Client::new(tls)
-        .login(imap_username, imap_password)
+    async_imap::Client::new(tls)
+        .login(&account.username, &password)
         .await
-        .map_err(|(e, _)| format!("Failed to login to IMAP server: {}", e))?;
-
-    // Selecting inbox and mails from yesterday
-    let yesterday = Local::now()
-        .checked_sub_signed(Duration::days(1))
-        .unwrap()
-        .format("%d-%b-%Y")
-        .to_string();
-    let inbox = imap
-        .select("INBOX")
+        .map_err(|(e, _)| format!("Failed to login to IMAP server: {}", e))
+}
+
+// Connects, logs in and opens each folder without fetching anything
+pub async fn check_connection(account: &Account) -> Result<String, String> {
+    let mut imap = connect(account).await?;
+
+    let mut counts = Vec::new();
+    for folder in &account.folders {
+        let mailbox = imap
+            .select(folder)
+            .await
+            .map_err(|e| format!("Failed to select {}: {}", folder, e))?;
+        counts.push(format!("{} has {} messages", folder, mailbox.exists));
+    }
+    let _ = imap.logout().await;
+
+    Ok(counts.join(", "))
+}
+
+pub async fn fetch_emails(
+    account: &Account,
+    since: NaiveDate,
+    progress: &Progress,
+) -> Result<Vec<Email>, String> {
+    progress.report(Stage::Connecting(account.server.clone()));
+    let mut imap = connect(account).await?;
+    progress.report(Stage::LoggedIn);
+
+    let mut fetch_emails: Vec<Email> = Vec::new();
+
+    for folder in &account.folders {
+        for email in fetch_folder(&mut imap, folder, since, progress).await? {
+            // The same mail can be filed in several folders
+            if !fetch_emails.iter().any(|known| known.id == email.id) {
+                fetch_emails.push(email);
+            }
+        }
+    }
+    let _ = imap.logout().await;
+
+    Ok(fetch_emails)
+}
+
+async fn fetch_folder(
+    imap: &mut async_imap::Session<Tls>,
+    folder: &str,
+    since: NaiveDate,
+    progress: &Progress,
+) -> Result<Vec<Email>, String> {
+    progress.report(Stage::Searching(folder.to_string()));
+
+    // Selecting the folder
+    imap.select(folder)
         .await
-        .map_err(|e| format!("Failed to select inbox: {}", e))?;
+        .map_err(|e| format!("Failed to select {}: {}", folder, e))?;
 
-    // Searching the inbox
-    let search_query = format!("SINCE {}", yesterday);
+    // Searching the folder
+    let search_query = format!("SINCE {}", since.format("%d-%b-%Y"));
     let mails = imap
         .search(&search_query)
         .await
-        .map_err(|e| format!("Failed to search inbox: {}", e))?;
-    if mails.len() == 0 {
+        .map_err(|e| format!("Failed to search {}: {}", folder, e))?;
+    if mails.is_empty() {
         return Ok(Vec::new());
     }
 
@@ -115,54 +296,80 @@ pub async fn fetch_emails() -> Result<Vec<Email>, String> {
         .collect::<Vec<String>>()
         .join(",");
 
+    let total = mails.len();
+    progress.report(Stage::Fetching { done: 0, total });
+
     let mut stream = imap
         .fetch(&sequence_set, "RFC822")
         .await
         .map_err(|e| format!("Failed to fetch emails: {}", e))?;
 
-    let mut fetch_emails = Vec::new();
+    let mut raw_emails = Vec::new();
 
     while let Some(result) = stream.next().await {
         match result {
             Ok(message) => {
-                let email_body = message.body().unwrap();
-                let parsed = mailparse::parse_mail(email_body)
-                    .map_err(|e| format!("Failed to parse mail: {}", e))?;
-
-                let subject = get_header_value(&parsed, "Subject")
-                    .unwrap_or_else(|| "(No Subject)".to_string());
-                let from = get_header_value(&parsed, "From")
-                    .unwrap_or_else(|| "(Unknown Sender)".to_string());
-                let body = extract_body(&parsed).unwrap_or_else(|_| "(No Body)".to_string());
-
-                fetch_emails.push(Email {
-                    subject,
-                    body,
-                    from,
+                if let Some(body) = message.body() {
+                    raw_emails.push(body.to_vec());
+                }
+                progress.report(Stage::Fetching {
+                    done: raw_emails.len(),
+                    total,
                 });
             }
             Err(e) => eprintln!("Error fetching a message: {}", e),
         }
     }
 
+    progress.report(Stage::Parsing(raw_emails.len()));
+    let mut fetch_emails = Vec::new();
+
+    for email_body in &raw_emails {
+        let parsed = mailparse::parse_mail(email_body)
+            .map_err(|e| format!("Failed to parse mail: {}", e))?;
+
+        let id = email_id(&parsed);
+        let subject =
+            get_header_value(&parsed, "Subject").unwrap_or_else(|| "(No Subject)".to_string());
+        let from =
+            get_header_value(&parsed, "From").unwrap_or_else(|| "(Unknown Sender)".to_string());
+        let to = get_header_value(&parsed, "To").unwrap_or_default();
+        let cc = get_header_value(&parsed, "Cc").unwrap_or_default();
+        let date = get_header_value(&parsed, "Date").unwrap_or_default();
+        let body = extract_body(&parsed).unwrap_or_else(|_| "(No Body)".to_string());
+        let attachments = attachments(&parsed);
+
+        fetch_emails.push(Email {
+            id,
+            subject,
+            body,
+            from,
+            to,
+            cc,
+            date,
+            attachments,
+        });
+    }
+
     Ok(fetch_emails)
 }
 
-pub fn email_formatter(emails: Vec<Email>) -> String {
+pub fn email_formatter(emails: &[Email]) -> String {
     if emails.is_empty() {
         return String::new();
     }
 
-    let formatted_emails = emails
+    emails
         .iter()
         .map(|email| {
             format!(
-                "Subject: {}\nFrom: {}\nBody: {}\n",
-                email.subject, email.from, email.body
+                "ID: {}\nSubject: {}\nFrom: {}\nBody: {}\n",
+                cite::reference(&email.id),
+                email.subject,
+                email.from,
+                email.body
             )
         })
         .collect::<Vec<String>>()
-        .join("-----------\n");
-
-    formatted_emails
+        .join("-----------\n")
 }
@@ -1,181 +1,1752 @@
-use chrono::Local;
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use clap::Parser;
+use config::{Config, Profile};
+use contacts::Contact;
 use directories::ProjectDirs;
 use dotenvy::dotenv;
-use iced::font::{Family, Stretch, Style, Weight};
-use iced::widget::button::background;
-use iced::widget::{button, column, container, row, scrollable, text};
-use iced::{Border, Element, Length, Padding, Task, Theme};
-use image;
+use history::{Briefing, Filter, History, Kind};
+use iced::widget::{
+    button, column, container, pick_list, row, scrollable, text, text_editor, text_input,
+};
+use iced::{Border, Element, Length, Padding, Subscription, Task, Theme};
+use job::{Job, Progress, Stage};
+use mail::Email;
+use palette::{Palette, Shortcut};
+use search::{Hit, Index, Target};
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
+use usage::UsageRecord;
 
+mod actions;
 mod ai;
+mod archive;
+mod cache;
+mod cards;
+mod cite;
+mod cli;
+mod config;
+mod contacts;
+mod export;
+mod fallback;
+mod fonts;
+mod history;
+mod job;
 mod mail;
+mod mute;
+mod palette;
+mod persist;
+mod prep;
+mod projects;
+mod prompt;
+mod schedule;
+mod search;
+mod secrets;
+mod settings;
+mod smtp;
+mod usage;
 
-const BODY_FONT: iced::Font = iced::Font {
-    family: iced::font::Family::Name("Pretendard Variable"),
-    weight: iced::font::Weight::Normal,
-    stretch: iced::font::Stretch::Normal,
-    style: iced::font::Style::Normal,
-};
+// Text colors derived from the active theme so both light and dark stay legible
+fn secondary_text(theme: &Theme) -> text::Style {
+    text::Style {
+        color: Some(theme.palette().text.scale_alpha(0.7)),
+    }
+}
+
+fn hint_text(theme: &Theme) -> text::Style {
+    text::Style {
+        color: Some(theme.palette().text.scale_alpha(0.45)),
+    }
+}
+
+fn warning_text(theme: &Theme) -> text::Style {
+    text::Style {
+        color: Some(theme.palette().warning),
+    }
+}
+
+// Compact secondary action beside an item or under a card
+fn small_button<'a>(label: impl Into<String>, message: Message) -> button::Button<'a, Message> {
+    button(
+        text(label.into())
+            .font(fonts::body())
+            .size(fonts::scaled(11))
+            .style(secondary_text),
+    )
+    .on_press(message)
+    .padding(Padding::from([2.0, 6.0]))
+    .style(|theme: &Theme, _state| button::Style {
+        background: Some(theme.palette().text.scale_alpha(0.05).into()),
+        border: Border {
+            radius: iced::border::Radius::from(4.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+}
+
+// Quiet title over a group of items
+fn section_heading<'a>(title: &'static str) -> iced::widget::Text<'a> {
+    text(title)
+        .font(fonts::body())
+        .size(fonts::scaled(13))
+        .style(hint_text)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-enum ActiveButton {
-    Previous,
-    Current,
+struct Tits {
+    summary: String,
+    last_updated: String,
+    // Briefing shown in the window, none while refreshing or on error
+    #[serde(default)]
+    selected: Option<u64>,
+    #[serde(default)]
+    usage_log: Vec<UsageRecord>,
+    #[serde(default)]
+    actions: actions::Tracker,
+    // Shown instead of the briefing
+    #[serde(skip)]
+    panel: Panel,
+    #[serde(skip)]
+    history: History,
+    #[serde(skip, default = "today")]
+    picker_date: NaiveDate,
+    #[serde(skip)]
+    index: Index,
+    #[serde(skip)]
+    search_query: String,
+    #[serde(skip)]
+    search_results: Option<Vec<Hit>>,
+    #[serde(skip)]
+    opened_email: Option<Email>,
+    // Briefings from the pre-history layout, imported on load
+    #[serde(default, skip_serializing)]
+    legacy_briefings: Vec<String>,
+    #[serde(skip)]
+    notice: Option<String>,
+    #[serde(default)]
+    last_scheduled_runs: BTreeMap<Kind, DateTime<Local>>,
+    #[serde(skip)]
+    schedules: Vec<(Kind, Vec<schedule::Rule>)>,
+    // Kind of briefing the Refresh button asks for
+    #[serde(default)]
+    refresh_kind: Kind,
+    // Kinds of briefing browsed with the arrows and the date picker
+    #[serde(default)]
+    history_filter: Filter,
+    // The refresh in progress, if any
+    #[serde(skip)]
+    job: Option<Job>,
+    #[serde(skip)]
+    export_menu: bool,
+    #[serde(skip)]
+    config: Config,
+    // Profile in use, from --profile or the config
+    #[serde(skip)]
+    profile: String,
+    // Open while the settings screen is shown
+    #[serde(skip)]
+    settings: Option<settings::Form>,
+    #[serde(skip)]
+    vault_passphrase: String,
+    // Email behind a citation, shown beside the briefing
+    #[serde(skip)]
+    source: Option<Email>,
+    // Decides the automatic layout; iced reports it once the window is open
+    #[serde(skip, default = "initial_width")]
+    window_width: f32,
+    // Open while the Ctrl+K palette replaces the briefing
+    #[serde(skip)]
+    palette: Option<Palette>,
+    // The shown briefing, one card per topic
+    #[serde(skip)]
+    cards: Vec<cards::Card>,
+    // Threads, senders and emails kept out of briefings
+    #[serde(default)]
+    muted: mute::Rules,
+    // What the user knows about the people who write to them
+    #[serde(default)]
+    contacts: contacts::Book,
+    // The contact being edited, with the address it was stored under
+    #[serde(skip)]
+    contact_form: Option<(String, Contact)>,
+    // Running summaries and timelines of the projects briefings follow
+    #[serde(default)]
+    projects: projects::Projects,
+    // Meetings the shown briefing lists, or those spotted in its mail when it lists none
+    #[serde(skip)]
+    meetings: Vec<fallback::Meeting>,
+    // Open while a meeting's prep replaces the briefing
+    #[serde(skip)]
+    prep: Option<prep::Dossier>,
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+fn initial_width() -> f32 {
+    iced::window::Settings::default().size.width
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    RefreshPressed,
+    RefreshKindSelected(Kind),
+    // Switches the kind and refreshes straight away, from the palette
+    RefreshAs(Kind),
+    HistoryFilterSelected(Filter),
+    RegeneratePressed,
+    SummaryGenerated(Result<Box<Briefing>, String>),
+    RefreshProgress(Stage),
+    CancelRefresh,
+    PreviousBriefing,
+    NextBriefing,
+    PickerPreviousDay,
+    PickerNextDay,
+    JumpToDate,
+    JumpTo(NaiveDate),
+    SearchChanged(String),
+    SearchSubmitted,
+    SearchCleared,
+    OpenHit(Target),
+    CloseEmail,
+    OpenSource(String),
+    CloseSource,
+    TogglePanel(Panel),
+    ForgetProject(String),
+    PrepMeeting(usize),
+    // The meeting's email id, and the note with what it cost
+    PrepWritten(String, Result<(String, UsageRecord), String>),
+    ClosePrep,
+    EditContact(String),
+    ContactEdited(contacts::Edit),
+    SaveContact,
+    CancelContact,
+    KeepContact(String),
+    RemoveContact(String),
+    ActionChecked(u64, bool),
+    SnoozeAction(u64, Option<NaiveDate>),
+    DismissNotice,
+    WindowResized(f32),
+    ScheduleTick,
+    ToggleExportMenu,
+    Export(export::Format),
+    BriefingSent(Result<usize, String>),
+    OpenSettings,
+    CloseSettings,
+    SettingsEdited(settings::Edit),
+    SaveSettings,
+    TestImap,
+    TestLlm,
+    ImapTested(Result<String, String>),
+    LlmTested(Result<String, String>),
+    ProfileSelected(String),
+    VaultPassphraseChanged(String),
+    UnlockVault,
+    CopyBriefing,
+    CopyCard(usize),
+    CardAction(usize, cards::CardAction),
+    Unmute(mute::Target),
+    CopyMarkdown,
+    CopyActionItem(u64),
+    CardEdited(usize, text_editor::Action),
+    CardSnoozeChanged(usize, String),
+    CardSnoozeSubmitted(usize),
+    Shortcut(Shortcut),
+    PaletteChanged(String),
+    PaletteSubmitted,
+    PaletteRun(usize),
+}
+
+const SEARCH_INPUT: &str = "search";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Panel {
+    #[default]
+    Briefing,
+    Actions,
+    People,
+    Projects,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Tits {
-    summary: String,
-    last_updated: String,
-    previous_briefing: Option<String>,
-    current_briefing: Option<String>,
-    previous_update: Option<String>,
-    update_time: Option<String>,
-    active: ActiveButton,
-}
+impl Panel {
+    // Each opens from its own button and closes back to the briefing
+    const OTHERS: [Panel; 3] = [Panel::Actions, Panel::People, Panel::Projects];
+
+    fn label(self) -> &'static str {
+        match self {
+            Panel::Briefing => "Briefing",
+            Panel::Actions => "Actions",
+            Panel::People => "People",
+            Panel::Projects => "Projects",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Panel::Briefing => "the briefing",
+            Panel::Actions => "action items",
+            Panel::People => "people",
+            Panel::Projects => "projects",
+        }
+    }
+}
+
+impl Default for Tits {
+    fn default() -> Self {
+        Self {
+            summary: String::from(
+                "You have a quiet morning. \n\n\
+                 There are no urgent blockers in your inbox. \n\n",
+            ),
+            last_updated: String::from("Last updated: Just now"),
+            selected: None,
+            usage_log: Vec::new(),
+            actions: actions::Tracker::default(),
+            panel: Panel::default(),
+            history: History::default(),
+            picker_date: today(),
+            index: Index::default(),
+            search_query: String::new(),
+            search_results: None,
+            opened_email: None,
+            legacy_briefings: Vec::new(),
+            notice: None,
+            last_scheduled_runs: BTreeMap::new(),
+            schedules: Vec::new(),
+            refresh_kind: Kind::default(),
+            history_filter: Filter::default(),
+            job: None,
+            export_menu: false,
+            config: Config::default(),
+            profile: String::new(),
+            settings: None,
+            vault_passphrase: String::new(),
+            source: None,
+            window_width: initial_width(),
+            palette: None,
+            cards: Vec::new(),
+            muted: mute::Rules::default(),
+            contacts: contacts::Book::default(),
+            contact_form: None,
+            projects: projects::Projects::default(),
+            meetings: Vec::new(),
+            prep: None,
+        }
+    }
+}
+
+impl Tits {
+    fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::RefreshPressed => self.start_refresh(self.refresh_kind, false),
+
+            Message::RegeneratePressed => self.start_refresh(self.refresh_kind, true),
+
+            Message::RefreshKindSelected(kind) => {
+                self.refresh_kind = kind;
+                self.save();
+
+                Task::none()
+            }
+
+            Message::RefreshAs(kind) => {
+                let _ = self.update(Message::RefreshKindSelected(kind));
+
+                self.update(Message::RefreshPressed)
+            }
+
+            Message::HistoryFilterSelected(filter) => {
+                self.history_filter = filter;
+                // Stay on the shown briefing if it still belongs, otherwise go to the latest that does
+                let shown = self.selected.and_then(|id| self.history.get(id));
+                if !shown.is_some_and(|briefing| filter.matches(briefing))
+                    && let Some(id) = self.history.latest(filter).map(|b| b.id)
+                {
+                    self.show_briefing(id);
+                }
+                self.save();
+
+                Task::none()
+            }
+
+            Message::SummaryGenerated(result) => {
+                // A result that arrives after cancelling is dropped
+                if self.job.take().is_none() {
+                    return Task::none();
+                }
+
+                // Nothing arrived in the window, or the mail was briefed already: nothing is
+                // stored, merged or delivered again
+                if let Ok(briefing) = &result {
+                    if briefing.is_empty() {
+                        self.last_updated = String::from("Updated: Just now (no new mail)");
+                        self.save();
+                        return Task::none();
+                    }
+                    if briefing.cached
+                        && let Some(id) = self.history.original(briefing)
+                    {
+                        self.show_briefing(id);
+                        self.last_updated =
+                            String::from("Updated: Just now (no new mail, cached briefing)");
+                        self.save();
+                        return Task::none();
+                    }
+                }
+
+                match result.and_then(|briefing| {
+                    let status = if briefing.degraded.is_some() {
+                        "Updated: Just now (offline summary)"
+                    } else if briefing.cached {
+                        "Updated: Just now (no new mail, cached briefing)"
+                    } else {
+                        "Updated: Just now"
+                    };
+                    self.usage_log.extend(briefing.usage.clone());
+                    self.history.push(*briefing).map(|id| (id, status))
+                }) {
+                    Ok((id, status)) => {
+                        let mut delivery = Task::none();
+                        if let Some(briefing) = self.history.get(id) {
+                            self.actions
+                                .merge(id, &briefing.action_items, briefing.created_at);
+                            self.projects
+                                .merge(id, &briefing.projects, briefing.created_at);
+                            self.index.add_briefing(briefing);
+                            let emails = briefing
+                                .source_email_ids
+                                .iter()
+                                .filter_map(|id| archive::load(id))
+                                .collect::<Vec<Email>>();
+                            for email in &emails {
+                                self.index.add_email(email);
+                            }
+                            self.contacts.suggest(&emails);
+
+                            // A cached briefing has already been delivered
+                            if smtp::enabled()
+                                && !briefing.cached
+                                && let Ok(profile) = self.config.resolve(&self.profile)
+                            {
+                                delivery = Task::perform(
+                                    smtp::send_briefing(profile.account, briefing.clone()),
+                                    Message::BriefingSent,
+                                );
+                            }
+                        }
+                        self.show_briefing(id);
+                        self.last_updated = String::from(status);
+                        self.save();
+
+                        return delivery;
+                    }
+                    Err(error) => {
+                        self.summary = format!("Error: {}", error);
+                        self.last_updated = String::from("Error");
+                    }
+                }
+
+                self.save();
+
+                Task::none()
+            }
+
+            Message::RefreshProgress(stage) => {
+                if self.job.is_some() {
+                    self.last_updated = stage.to_string();
+                }
+
+                Task::none()
+            }
+
+            Message::CancelRefresh => {
+                let Some(job) = self.job.take() else {
+                    return Task::none();
+                };
+                job.cancel();
+
+                match self.history.latest(self.history_filter).map(|b| b.id) {
+                    Some(id) => self.show_briefing(id),
+                    None => self.summary = String::new(),
+                }
+                self.last_updated = String::from("Refresh cancelled");
+                self.save();
+
+                Task::none()
+            }
+
+            Message::PreviousBriefing => {
+                let older = match self.selected {
+                    Some(id) => self.history.older(id, self.history_filter),
+                    None => self.history.latest(self.history_filter),
+                };
+                if let Some(id) = older.map(|b| b.id) {
+                    self.show_briefing(id);
+                    self.save();
+                }
+
+                Task::none()
+            }
+
+            Message::NextBriefing => {
+                let newer = self
+                    .selected
+                    .and_then(|id| self.history.newer(id, self.history_filter));
+                if let Some(id) = newer.map(|b| b.id) {
+                    self.show_briefing(id);
+                    self.save();
+                }
+
+                Task::none()
+            }
+
+            Message::PickerPreviousDay => {
+                self.picker_date -= Duration::days(1);
+
+                Task::none()
+            }
+
+            Message::PickerNextDay => {
+                if self.picker_date < today() {
+                    self.picker_date += Duration::days(1);
+                }
+
+                Task::none()
+            }
+
+            Message::JumpTo(date) => {
+                self.picker_date = date;
+
+                self.update(Message::JumpToDate)
+            }
+
+            Message::JumpToDate => {
+                match self
+                    .history
+                    .on_date(self.picker_date, self.history_filter)
+                    .map(|b| b.id)
+                {
+                    Some(id) => {
+                        self.show_briefing(id);
+                        self.save();
+                    }
+                    None => {
+                        self.last_updated =
+                            format!("No briefings on {}", self.picker_date.format("%b %-d, %Y"));
+                    }
+                }
+
+                Task::none()
+            }
+
+            Message::SearchChanged(query) => {
+                self.search_query = query;
+
+                Task::none()
+            }
+
+            Message::SearchSubmitted => {
+                self.opened_email = None;
+                self.search_results = if self.search_query.trim().is_empty() {
+                    None
+                } else {
+                    Some(self.index.search(&self.search_query))
+                };
+
+                Task::none()
+            }
+
+            Message::SearchCleared => {
+                self.search_query.clear();
+                self.search_results = None;
+                self.opened_email = None;
+
+                Task::none()
+            }
+
+            Message::OpenHit(target) => {
+                match target {
+                    Target::Briefing(id) => {
+                        self.search_results = None;
+                        self.panel = Panel::Briefing;
+                        self.show_briefing(id);
+                        self.save();
+                    }
+                    Target::Email(id) => match archive::load(&id) {
+                        Some(email) => self.opened_email = Some(email),
+                        None => self.last_updated = String::from("Email is no longer cached"),
+                    },
+                }
+
+                Task::none()
+            }
+
+            Message::CloseEmail => {
+                self.opened_email = None;
+
+                Task::none()
+            }
+
+            Message::OpenSource(reference) => {
+                let shown = self.selected.and_then(|id| self.history.get(id));
+                match shown
+                    .and_then(|briefing| cite::source(briefing, &reference))
+                    .and_then(|(_, id)| archive::load(&id))
+                {
+                    Some(email) => self.source = Some(email),
+                    None => self.last_updated = String::from("Email is no longer cached"),
+                }
+
+                Task::none()
+            }
+
+            Message::CloseSource => {
+                self.source = None;
+
+                Task::none()
+            }
+
+            Message::DismissNotice => {
+                self.notice = None;
+
+                Task::none()
+            }
+
+            Message::WindowResized(width) => {
+                self.window_width = width;
+
+                Task::none()
+            }
+
+            Message::ToggleExportMenu => {
+                self.export_menu = !self.export_menu;
+
+                Task::none()
+            }
+
+            Message::Export(format) => {
+                self.export_menu = false;
+
+                if let Some(briefing) = self.selected.and_then(|id| self.history.get(id)) {
+                    self.last_updated = match export::export(briefing, format, None) {
+                        Ok(path) => format!("Exported to {}", path.display()),
+                        Err(e) => format!("Export failed: {}", e),
+                    };
+                }
+
+                Task::none()
+            }
+
+            Message::BriefingSent(result) => {
+                match result {
+                    Ok(recipients) => {
+                        self.last_updated = format!(
+                            "{} · emailed to {} recipients",
+                            self.last_updated, recipients
+                        )
+                    }
+                    Err(e) => self.notice = Some(e),
+                }
+
+                Task::none()
+            }
+
+            Message::TogglePanel(panel) => {
+                self.panel = if self.panel == panel {
+                    Panel::Briefing
+                } else {
+                    panel
+                };
+                self.contact_form = None;
+                self.opened_email = None;
+
+                Task::none()
+            }
+
+            Message::ForgetProject(name) => {
+                self.projects.remove(&name);
+                self.save();
+
+                Task::none()
+            }
+
+            // The dossier is gathered at once; the note on top is written by the model meanwhile
+            Message::PrepMeeting(index) => {
+                let Some(meeting) = self.meetings.get(index) else {
+                    return Task::none();
+                };
+                let profile = self
+                    .config
+                    .resolve(&self.profile)
+                    .ok()
+                    .filter(|profile| profile.problems().is_empty());
+                let user = profile
+                    .as_ref()
+                    .and_then(|profile| fallback::user_address(&profile.account));
+
+                let mut dossier = prep::build(
+                    meeting,
+                    &self.index,
+                    &self.contacts,
+                    &self.actions,
+                    user.as_deref(),
+                    today(),
+                );
+                let budget = usage::monthly_budget()
+                    .filter(|budget| usage::monthly_total(&self.usage_log) >= *budget);
+                let task = match (profile, budget) {
+                    (None, _) => {
+                        dossier.note = Some(Err(String::from(
+                            "Finish the settings to have a prep note written",
+                        )));
+                        Task::none()
+                    }
+                    (Some(_), Some(budget)) => {
+                        dossier.note =
+                            Some(Err(format!("Monthly budget of ${:.2} reached", budget)));
+                        Task::none()
+                    }
+                    (Some(profile), None) => {
+                        let prompt = prompt::prep_prompt(&dossier);
+                        let id = meeting.email_id.clone();
+                        Task::perform(
+                            async move {
+                                ai::generate_response(&profile.llm, prompt, &Progress::default())
+                                    .await
+                                    .map(|(text, usage)| {
+                                        (text, UsageRecord::new(&profile.llm.model, usage))
+                                    })
+                            },
+                            move |result| Message::PrepWritten(id.clone(), result),
+                        )
+                    }
+                };
+                self.opened_email = None;
+                self.prep = Some(dossier);
+
+                task
+            }
+
+            // Paid for even if the prep was closed meanwhile
+            Message::PrepWritten(id, result) => {
+                let note = result.map(|(text, record)| {
+                    self.usage_log.push(record);
+                    text
+                });
+                if let Some(dossier) = &mut self.prep
+                    && dossier.meeting.email_id == id
+                {
+                    dossier.note = Some(note);
+                }
+                self.save();
+
+                Task::none()
+            }
+
+            Message::ClosePrep => {
+                self.prep = None;
+
+                Task::none()
+            }
+
+            // An unknown address starts a new contact
+            Message::EditContact(address) => {
+                let contact = self.contacts.get(&address).cloned().unwrap_or_default();
+                self.contact_form = Some((address, contact));
+
+                Task::none()
+            }
+
+            Message::ContactEdited(edit) => {
+                if let Some((_, contact)) = &mut self.contact_form {
+                    contact.apply(edit);
+                }
+
+                Task::none()
+            }
+
+            Message::SaveContact => {
+                let Some((address, contact)) = self.contact_form.take() else {
+                    return Task::none();
+                };
+                match self.contacts.save(&address, contact.clone()) {
+                    Ok(()) => self.save(),
+                    Err(e) => {
+                        self.notice = Some(e);
+                        self.contact_form = Some((address, contact));
+                    }
+                }
+
+                Task::none()
+            }
+
+            Message::CancelContact => {
+                self.contact_form = None;
+
+                Task::none()
+            }
+
+            Message::KeepContact(address) => {
+                if let Some(contact) = self.contacts.get(&address).cloned()
+                    && self.contacts.save(&address, contact).is_ok()
+                {
+                    self.save();
+                }
+
+                Task::none()
+            }
+
+            Message::RemoveContact(address) => {
+                self.contacts.remove(&address);
+                self.save();
+
+                Task::none()
+            }
+
+            Message::CopyBriefing => self.copy_as(export::Format::Text),
+
+            Message::CopyMarkdown => self.copy_as(export::Format::Markdown),
+
+            Message::CopyCard(index) => match self.cards.get(index) {
+                Some(card) => {
+                    self.last_updated = String::from("Section copied");
+                    iced::clipboard::write(card.text.text())
+                }
+                None => Task::none(),
+            },
+
+            // The rule covers every email the card cites
+            Message::CardAction(index, action) => {
+                let Some(card) = self.cards.get(index) else {
+                    return Task::none();
+                };
+
+                for email in &card.emails {
+                    match action {
+                        cards::CardAction::MuteThread => self.muted.add(
+                            mute::Target::thread(email),
+                            mute::Reason::Muted,
+                            &email.subject,
+                            None,
+                        ),
+                        cards::CardAction::MuteSender => self.muted.add(
+                            mute::Target::sender(email),
+                            mute::Reason::Muted,
+                            &fallback::address(&email.from),
+                            None,
+                        ),
+                        cards::CardAction::Snooze(until) => self.muted.add(
+                            mute::Target::thread(email),
+                            mute::Reason::Snoozed,
+                            &email.subject,
+                            Some(until),
+                        ),
+                        // Later replies in the thread still come through
+                        cards::CardAction::Handled => self.muted.add(
+                            mute::Target::Email(email.id.clone()),
+                            mute::Reason::Handled,
+                            &email.subject,
+                            Some(today() + Duration::days(cards::HANDLED_DAYS)),
+                        ),
+                        cards::CardAction::Undo => self.muted.release(email),
+                    }
+                }
+                self.save();
+
+                Task::none()
+            }
+
+            Message::Unmute(target) => {
+                self.muted.remove(&target);
+                self.save();
+
+                Task::none()
+            }
+
+            Message::CopyActionItem(id) => {
+                let Some(item) = self.actions.all().iter().find(|item| item.id == id) else {
+                    return Task::none();
+                };
+                let detail = item.describe();
+                let text = if detail.is_empty() {
+                    item.task.clone()
+                } else {
+                    format!("{} ({})", item.task, detail)
+                };
+                self.last_updated = String::from("Action item copied");
+
+                iced::clipboard::write(text)
+            }
+
+            // Selection and cursor movement only; the briefing itself cannot be edited
+            Message::CardEdited(index, action) => {
+                if !action.is_edit()
+                    && let Some(card) = self.cards.get_mut(index)
+                {
+                    card.text.perform(action);
+                }
+
+                Task::none()
+            }
+
+            Message::CardSnoozeChanged(index, query) => {
+                if let Some(card) = self.cards.get_mut(index) {
+                    card.snooze = query;
+                }
+
+                Task::none()
+            }
+
+            Message::CardSnoozeSubmitted(index) => {
+                let Some(card) = self.cards.get_mut(index) else {
+                    return Task::none();
+                };
+                match palette::date_ahead(&card.snooze) {
+                    Some(until) if until > today() => {
+                        card.snooze.clear();
+                        return self
+                            .update(Message::CardAction(index, cards::CardAction::Snooze(until)));
+                    }
+                    _ => {
+                        self.last_updated =
+                            format!("Cannot snooze until \"{}\"", card.snooze.trim())
+                    }
+                }
+
+                Task::none()
+            }
+
+            Message::Shortcut(shortcut) => self.run_shortcut(shortcut),
+
+            Message::PaletteChanged(query) => {
+                if let Some(palette) = &mut self.palette {
+                    palette.query = query;
+                    palette.selected = 0;
+                }
+
+                Task::none()
+            }
+
+            Message::PaletteSubmitted => {
+                let selected = self.palette.as_ref().map_or(0, |palette| palette.selected);
+
+                self.update(Message::PaletteRun(selected))
+            }
+
+            Message::PaletteRun(index) => {
+                let entry = self
+                    .palette_entries()
+                    .into_iter()
+                    .take(palette::SHOWN)
+                    .nth(index);
+                self.palette = None;
+
+                match entry {
+                    Some(entry) => self.update(entry.message),
+                    None => Task::none(),
+                }
+            }
+
+            Message::ActionChecked(id, done) => {
+                self.actions.set_done(id, done);
+                self.save();
+
+                Task::none()
+            }
+
+            Message::SnoozeAction(id, until) => {
+                self.actions.snooze(id, until);
+                self.save();
+
+                Task::none()
+            }
+
+            Message::OpenSettings => {
+                self.settings = Some(settings::Form::new(&self.config, &self.profile));
+
+                Task::none()
+            }
+
+            Message::CloseSettings => {
+                self.settings = None;
+                fonts::apply(&self.config);
+
+                Task::none()
+            }
+
+            Message::SettingsEdited(edit) => {
+                if let Some(form) = &mut self.settings {
+                    form.apply(edit);
+                    fonts::apply(&form.config);
+                }
+
+                Task::none()
+            }
+
+            Message::SaveSettings => {
+                let Some(mut form) = self.settings.take() else {
+                    return Task::none();
+                };
+                form.config.active = form.profile.clone();
+
+                match form.store_secrets().and_then(|_| form.config.save()) {
+                    Ok(()) => {
+                        self.schedules = form.config.schedules().unwrap_or_default();
+                        self.profile = form.profile;
+                        self.config = form.config;
+                        self.last_updated = String::from("Settings saved");
+                    }
+                    Err(e) => {
+                        self.notice = Some(format!("Could not save settings: {}", e));
+                        self.settings = Some(form);
+                    }
+                }
+
+                Task::none()
+            }
+
+            Message::TestImap => {
+                let Some(form) = &mut self.settings else {
+                    return Task::none();
+                };
+                form.imap_test = settings::Test::Running;
+                let profile = form.test_profile();
+
+                Task::perform(
+                    async move { mail::check_connection(&profile?.account).await },
+                    Message::ImapTested,
+                )
+            }
+
+            Message::TestLlm => {
+                let Some(form) = &mut self.settings else {
+                    return Task::none();
+                };
+                form.llm_test = settings::Test::Running;
+                let profile = form.test_profile();
+
+                Task::perform(
+                    async move { ai::check_connection(&profile?.llm).await },
+                    Message::LlmTested,
+                )
+            }
+
+            Message::ImapTested(result) => {
+                // Ignored when the form was closed or edited in the meantime
+                if let Some(form) = &mut self.settings
+                    && matches!(form.imap_test, settings::Test::Running)
+                {
+                    form.imap_test = settings::Test::Done(result);
+                }
+
+                Task::none()
+            }
+
+            Message::LlmTested(result) => {
+                if let Some(form) = &mut self.settings
+                    && matches!(form.llm_test, settings::Test::Running)
+                {
+                    form.llm_test = settings::Test::Done(result);
+                }
+
+                Task::none()
+            }
+
+            Message::ProfileSelected(name) => {
+                self.config.active = name.clone();
+                self.profile = name;
+                if let Err(e) = self.config.save() {
+                    self.notice = Some(format!("Could not save settings: {}", e));
+                }
+
+                Task::none()
+            }
+
+            Message::VaultPassphraseChanged(passphrase) => {
+                self.vault_passphrase = passphrase;
+
+                Task::none()
+            }
+
+            Message::UnlockVault => {
+                let passphrase = std::mem::take(&mut self.vault_passphrase);
+                self.last_updated = match secrets::unlock(passphrase) {
+                    Ok(()) => String::from("Vault unlocked"),
+                    Err(e) => e,
+                };
+
+                Task::none()
+            }
+
+            Message::ScheduleTick => {
+                let now = Local::now();
+
+                for (kind, rules) in self.schedules.clone() {
+                    let Some(due) = schedule::last_due(&rules, now) else {
+                        continue;
+                    };
+
+                    match self.last_scheduled_runs.get(&kind) {
+                        // Only the latest missed slot is caught up, not every one since. A kind
+                        // that falls due while another runs is picked up on a later tick, and so
+                        // is one that could not start, so the slot is only spent on a real run.
+                        // The open settings form is left alone until it is closed.
+                        Some(last_run)
+                            if due > *last_run && self.job.is_none() && self.settings.is_none() =>
+                        {
+                            let task = self.start_refresh(kind, false);
+                            if self.job.is_some() {
+                                self.last_scheduled_runs.insert(kind, due);
+                                self.save();
+                            }
+                            return task;
+                        }
+                        Some(_) => {}
+                        // A freshly configured schedule starts counting from now
+                        None => {
+                            self.last_scheduled_runs.insert(kind, due);
+                            self.save();
+                        }
+                    }
+                }
+
+                Task::none()
+            }
+        }
+    }
 
-#[derive(Debug, Clone)]
-enum Message {
-    RefreshPressed,
-    SummaryGenerated(Result<String, String>),
-    PreviousBriefing,
-    CurrentBriefing,
-}
+    fn show_briefing(&mut self, id: u64) {
+        let Some(briefing) = self.history.get(id) else {
+            return;
+        };
 
-impl Default for Tits {
-    fn default() -> Self {
-        Self {
-            summary: String::from(
-                "You have a quiet morning. \n\n\
-                 There are no urgent blockers in your inbox. \n\n",
-            ),
-            last_updated: String::from("Last updated: Just now"),
-            previous_briefing: None,
-            current_briefing: None,
-            previous_update: None,
-            update_time: None,
-            active: ActiveButton::Current,
+        let emails = briefing
+            .source_email_ids
+            .iter()
+            .filter_map(|id| archive::load(id))
+            .collect::<Vec<Email>>();
+        self.summary = briefing.text.clone();
+        self.cards = briefing
+            .paragraphs()
+            .into_iter()
+            .zip(briefing.sections())
+            .map(|(paragraph, section)| {
+                cards::Card::new(briefing, &emails, paragraph, &section.text)
+            })
+            .collect();
+        // Marked against the daily briefing before, like the action items
+        let before = self
+            .history
+            .all()
+            .iter()
+            .rev()
+            .filter(|b| b.id < id && b.kind != Kind::Weekly)
+            .find(|_| briefing.kind != Kind::Weekly);
+        if let Some(before) = before {
+            for card in &mut self.cards {
+                card.new = !card.emails.is_empty()
+                    && card
+                        .emails
+                        .iter()
+                        .all(|email| !before.source_email_ids.contains(&email.id));
+            }
         }
+        self.meetings = briefing
+            .meetings
+            .clone()
+            .unwrap_or_else(|| fallback::find_meetings(&emails));
+        self.last_updated = format!("Last Updated at: {}", briefing.label());
+        self.opened_email = None;
+        self.source = None;
+        self.prep = None;
+        self.picker_date = briefing.created_at.date_naive();
+        self.selected = Some(id);
     }
-}
 
-impl Tits {
-    fn update(&mut self, message: Message) -> Task<Message> {
-        match message {
-            Message::RefreshPressed => {
-                self.previous_briefing = Some(self.summary.clone());
+    // Snoozed items stay out of the prompt until they wake up. Daily briefings compare against
+    // the last daily one; the weekly digest gets the week's briefings instead.
+    fn prompt_context(&self, kind: Kind, delta: bool) -> prompt::Context {
+        let open_items = self.actions.pending(today()).into_iter().cloned().collect();
 
-                let now = Local::now();
-                let formatted = now.format("%b %-d, %-I:%M %p").to_string();
-                self.previous_update = self.update_time.take();
-                self.update_time = Some(formatted);
+        if kind == Kind::Weekly {
+            let since =
+                Local::now() - Duration::hours(self.config.briefings.weekly.window_hours.into());
+            return prompt::Context {
+                open_items,
+                briefings: self
+                    .history
+                    .all()
+                    .iter()
+                    .filter(|b| b.kind != Kind::Weekly && b.created_at >= since)
+                    .cloned()
+                    .collect(),
+                done_items: self
+                    .actions
+                    .all()
+                    .iter()
+                    .filter(|item| item.done_at.is_some_and(|done| done >= since))
+                    .cloned()
+                    .collect(),
+                ..prompt::Context::default()
+            };
+        }
+
+        prompt::Context {
+            open_items,
+            previous: self
+                .history
+                .all()
+                .iter()
+                .rev()
+                .find(|b| b.kind != Kind::Weekly)
+                .map(|briefing| prompt::Previous {
+                    id: briefing.id,
+                    created_at: briefing.created_at,
+                    items: briefing.action_items.clone(),
+                }),
+            delta,
+            contacts: self.contacts.clone(),
+            projects: self.projects.active(Local::now()),
+            ..prompt::Context::default()
+        }
+    }
 
-                self.last_updated = String::from("Refreshing...");
-                self.summary = String::from("Reading inbox...");
+    // The whole briefing with its numbered sources, as exported
+    fn copy_as(&mut self, format: export::Format) -> Task<Message> {
+        let Some(briefing) = self.selected.and_then(|id| self.history.get(id)) else {
+            return Task::none();
+        };
+        let text = String::from_utf8_lossy(&export::render(briefing, format)).into_owned();
+        self.last_updated = match format {
+            export::Format::Text => String::from("Briefing copied"),
+            format => format!("Briefing copied as {}", format.label()),
+        };
 
-                self.save();
+        iced::clipboard::write(text)
+    }
 
-                Task::perform(refresh_inbox(), Message::SummaryGenerated)
-            }
+    // Shortcuts act on what is in front: the palette first, then a running refresh, then
+    // whichever pane is open
+    fn run_shortcut(&mut self, shortcut: Shortcut) -> Task<Message> {
+        if self.palette.is_some() {
+            let count = self.palette_entries().len().min(palette::SHOWN);
+            let Some(palette) = &mut self.palette else {
+                return Task::none();
+            };
 
-            Message::SummaryGenerated(result) => {
-                match result {
-                    Ok(text) => {
-                        self.summary = text;
-                        self.current_briefing = Some(self.summary.clone());
-                        self.last_updated = String::from("Updated: Just now");
-                    }
-                    Err(error) => {
-                        self.summary = format!("Error: {}", error);
-                        self.current_briefing = Some(self.summary.clone());
-                        self.last_updated = String::from("Error");
-                    }
+            match shortcut {
+                Shortcut::Up => palette.selected = palette.selected.saturating_sub(1),
+                Shortcut::Down => palette.selected = (palette.selected + 1).min(count.max(1) - 1),
+                Shortcut::Palette | Shortcut::Cancel => self.palette = None,
+                // The arrows belong to the palette's text field while it is open
+                Shortcut::Previous | Shortcut::Next => {}
+                _ => {
+                    self.palette = None;
+                    return self.run_shortcut(shortcut);
                 }
+            }
 
-                self.active = ActiveButton::Current;
-                self.save();
+            return Task::none();
+        }
 
-                Task::none()
+        match shortcut {
+            Shortcut::Palette => {
+                self.palette = Some(Palette::default());
+                iced::widget::operation::focus(palette::INPUT)
             }
+            Shortcut::Refresh if self.job.is_none() => self.update(Message::RefreshPressed),
+            Shortcut::Previous => self.update(Message::PreviousBriefing),
+            Shortcut::Next => self.update(Message::NextBriefing),
+            Shortcut::Search => iced::widget::operation::focus(SEARCH_INPUT),
+            Shortcut::Copy => self.update(Message::CopyBriefing),
+            Shortcut::Settings if self.settings.is_none() => self.update(Message::OpenSettings),
+            Shortcut::Cancel => {
+                let close = if self.job.is_some() {
+                    Message::CancelRefresh
+                } else if self.export_menu {
+                    Message::ToggleExportMenu
+                } else if self.source.is_some() {
+                    Message::CloseSource
+                } else if self.opened_email.is_some() {
+                    Message::CloseEmail
+                } else if self.prep.is_some() {
+                    Message::ClosePrep
+                } else if self.search_results.is_some() {
+                    Message::SearchCleared
+                } else if self.settings.is_some() {
+                    Message::CloseSettings
+                } else if self.contact_form.is_some() {
+                    Message::CancelContact
+                } else if self.panel != Panel::Briefing {
+                    Message::TogglePanel(self.panel)
+                } else {
+                    return Task::none();
+                };
 
-            Message::PreviousBriefing => {
-                self.summary = self.previous_briefing.clone().unwrap_or_default();
+                self.update(close)
+            }
+            _ => Task::none(),
+        }
+    }
 
-                let last = self
-                    .previous_update
-                    .clone()
-                    .unwrap_or_else(|| "Forever ago".to_string());
-                self.last_updated = format!("Last Updated at: {}", last);
+    // Everything the palette offers, in the order shown before anything is typed. A typed date
+    // goes first.
+    fn palette_entries(&self) -> Vec<palette::Entry> {
+        use palette::Entry;
 
-                self.active = ActiveButton::Previous;
-                self.save();
+        let query = self
+            .palette
+            .as_ref()
+            .map_or("", |palette| palette.query.as_str());
+        let idle = self.job.is_none();
+        let shown = self.selected.is_some();
 
-                Task::none()
-            }
+        let mut entries = Vec::new();
+        if idle {
+            entries.push(Entry::new(
+                "Refresh",
+                Shortcut::Refresh.keys(),
+                Message::RefreshPressed,
+            ));
+            entries.push(Entry::new("Regenerate", "", Message::RegeneratePressed));
+        } else {
+            entries.push(Entry::new(
+                "Cancel refresh",
+                Shortcut::Cancel.keys(),
+                Message::CancelRefresh,
+            ));
+        }
+        entries.extend([
+            Entry::new(
+                "Previous briefing",
+                Shortcut::Previous.keys(),
+                Message::PreviousBriefing,
+            ),
+            Entry::new(
+                "Next briefing",
+                Shortcut::Next.keys(),
+                Message::NextBriefing,
+            ),
+            Entry::new(
+                "Search",
+                Shortcut::Search.keys(),
+                Message::Shortcut(Shortcut::Search),
+            ),
+            Entry::new("Settings", Shortcut::Settings.keys(), Message::OpenSettings),
+        ]);
+        entries.extend(Panel::OTHERS.into_iter().map(|panel| {
+            let shows = if self.panel == panel {
+                Panel::Briefing
+            } else {
+                panel
+            };
+            Entry::new(
+                format!("Show {}", shows.describe()),
+                "",
+                Message::TogglePanel(panel),
+            )
+        }));
+        if shown {
+            entries.push(Entry::new(
+                "Copy briefing",
+                Shortcut::Copy.keys(),
+                Message::CopyBriefing,
+            ));
+            entries.extend(export::Format::ALL.into_iter().map(|format| {
+                Entry::new(
+                    format!("Export as {}", format.label()),
+                    "",
+                    Message::Export(format),
+                )
+            }));
+            entries.extend(self.meetings.iter().enumerate().map(|(index, meeting)| {
+                Entry::new(
+                    format!("Prep for {}", meeting.title),
+                    meeting.when.clone(),
+                    Message::PrepMeeting(index),
+                )
+            }));
+        }
+        entries.extend(Kind::ALL.into_iter().map(|kind| {
+            Entry::new(
+                format!("Refresh as {} briefing", kind.to_string().to_lowercase()),
+                "",
+                Message::RefreshAs(kind),
+            )
+        }));
+        entries.extend(Filter::ALL.into_iter().map(|filter| {
+            Entry::new(
+                match filter {
+                    Filter::All => String::from("Browse all briefings"),
+                    Filter::Only(kind) => {
+                        format!("Browse {} briefings only", kind.to_string().to_lowercase())
+                    }
+                },
+                "",
+                Message::HistoryFilterSelected(filter),
+            )
+        }));
+        entries.extend(self.history.all().iter().rev().map(|briefing| {
+            Entry::new(
+                format!("Go to {}", briefing.title()),
+                "",
+                Message::OpenHit(Target::Briefing(briefing.id)),
+            )
+        }));
 
-            Message::CurrentBriefing => {
-                self.summary = self.current_briefing.clone().unwrap_or_default();
+        let mut entries = palette::filter(query, entries);
+        if let Some(date) = palette::date(query) {
+            entries.insert(
+                0,
+                Entry::new(
+                    format!("Go to {}", date.format("%a, %b %-d %Y")),
+                    "Date",
+                    Message::JumpTo(date),
+                ),
+            );
+        }
 
-                let last = self
-                    .update_time
-                    .clone()
-                    .unwrap_or_else(|| "Forever ago".to_string());
-                self.last_updated = format!("Last Updated at: {}", last);
+        entries
+    }
 
-                self.active = ActiveButton::Current;
-                self.save();
+    fn start_refresh(&mut self, kind: Kind, force: bool) -> Task<Message> {
+        if self.job.is_some() {
+            return Task::none();
+        }
 
-                Task::none()
+        let profile = match self.config.resolve(&self.profile) {
+            Ok(profile) if profile.problems().is_empty() => profile,
+            _ => {
+                self.settings = Some(settings::Form::new(&self.config, &self.profile));
+                self.last_updated = String::from("Finish the settings before refreshing");
+                return Task::none();
             }
+        };
+
+        if let Some(budget) = usage::monthly_budget()
+            && usage::monthly_total(&self.usage_log) >= budget
+        {
+            self.last_updated = format!("Monthly budget of ${:.2} reached", budget);
+            self.save();
+            return Task::none();
         }
+
+        self.muted.prune(today());
+        self.selected = None;
+        self.last_updated = format!(
+            "Refreshing the {} briefing...",
+            kind.to_string().to_lowercase()
+        );
+        self.summary = String::from(match kind {
+            Kind::Weekly => "Reading the week's briefings...",
+            _ => "Reading inbox...",
+        });
+
+        self.save();
+
+        let (job, task) = Job::start(
+            profile,
+            kind,
+            self.config.briefings.get(kind).window_hours,
+            force,
+            self.prompt_context(kind, self.config.delta),
+            self.muted.clone(),
+        );
+        self.job = Some(job);
+
+        task
     }
 
-    fn view(&self) -> Element<Message> {
-        let btn_previous = match (self.active.clone(), self.previous_briefing.is_some()) {
-            (ActiveButton::Previous, _) => button("<"),
-            (_, true) => button("<").on_press(Message::PreviousBriefing),
-            _ => button("<"),
+    fn view(&self) -> Element<'_, Message> {
+        let shown = self.selected.and_then(|id| self.history.get(id));
+
+        let filter = self.history_filter;
+        let has_older = match shown {
+            Some(briefing) => self.history.older(briefing.id, filter).is_some(),
+            None => self.history.latest(filter).is_some(),
         };
-        let btn_next = match (self.active.clone(), self.current_briefing.is_some()) {
-            (ActiveButton::Current, _) => button(">"),
-            (_, true) => button(">").on_press(Message::CurrentBriefing),
-            _ => button(">"),
+        let has_newer = shown.is_some_and(|b| self.history.newer(b.id, filter).is_some());
+
+        let btn_previous =
+            button("<").on_press_maybe(has_older.then_some(Message::PreviousBriefing));
+        let btn_next = button(">").on_press_maybe(has_newer.then_some(Message::NextBriefing));
+
+        let position = shown
+            .and_then(|b| self.history.rank(b.id, filter))
+            .map(|(rank, count)| format!("{} of {}", rank + 1, count))
+            .unwrap_or_default();
+
+        let picker_button = |label: &'static str, message: Option<Message>| {
+            button(
+                text(label)
+                    .font(fonts::body())
+                    .size(fonts::scaled(11))
+                    .style(secondary_text),
+            )
+            .on_press_maybe(message)
+            .style(|theme: &Theme, _state| button::Style {
+                background: Some(theme.palette().background.into()),
+                ..Default::default()
+            })
+        };
+        let now = Local::now();
+        let next_run = self
+            .schedules
+            .iter()
+            .filter_map(|(kind, rules)| schedule::next_due(rules, now).map(|next| (next, *kind)))
+            .min()
+            .map(|(next, kind)| format!("Next: {} at {}", kind, next.format("%a %-I:%M %p")))
+            .unwrap_or_default();
+
+        let date_picker = row![
+            picker_button("‹", Some(Message::PickerPreviousDay)),
+            text(self.picker_date.format("%a, %b %-d %Y").to_string())
+                .font(fonts::body())
+                .size(fonts::scaled(11)),
+            picker_button(
+                "›",
+                (self.picker_date < today()).then_some(Message::PickerNextDay)
+            ),
+            picker_button("Go", Some(Message::JumpToDate)),
+            pick_list(Filter::ALL, Some(filter), Message::HistoryFilterSelected)
+                .font(fonts::body())
+                .text_size(fonts::scaled(11)),
+            text(position)
+                .font(fonts::body())
+                .size(fonts::scaled(11))
+                .style(hint_text),
+            text(next_run)
+                .font(fonts::body())
+                .size(fonts::scaled(11))
+                .style(hint_text),
+        ]
+        .spacing(6)
+        .align_y(iced::Alignment::Center)
+        .wrap();
+
+        let shown_usage = shown.and_then(|b| b.usage.as_ref());
+        let degraded_notice = shown.and_then(|b| b.degraded.as_ref()).map(|reason| {
+            text(format!(
+                "Offline summary: the assistant could not be reached ({})",
+                reason
+            ))
+            .font(fonts::body())
+            .size(fonts::scaled(12))
+            .style(warning_text)
+        });
+        let mut usage_line = shown_usage.map(UsageRecord::describe).unwrap_or_default();
+        if !usage_line.is_empty() {
+            usage_line.push_str("  |  ");
+        }
+        usage_line.push_str(&format!(
+            "Today: ${:.4} · This month: ${:.4}",
+            usage::daily_total(&self.usage_log),
+            usage::monthly_total(&self.usage_log)
+        ));
+        if let Some(budget) = usage::monthly_budget() {
+            usage_line.push_str(&format!(" of ${:.2}", budget));
+        }
+
+        let search_bar = row![
+            text_input("Search briefings and mail", &self.search_query)
+                .font(fonts::body())
+                .size(fonts::scaled(13))
+                .id(SEARCH_INPUT)
+                .on_input(Message::SearchChanged)
+                .on_submit(Message::SearchSubmitted),
+            picker_button(
+                "Clear",
+                (self.search_results.is_some() || self.opened_email.is_some())
+                    .then_some(Message::SearchCleared)
+            ),
+        ]
+        .spacing(6)
+        .align_y(iced::Alignment::Center);
+
+        let body: Element<Message> = if let Some(palette) = &self.palette {
+            self.palette_view(palette)
+        } else if let Some(form) = &self.settings {
+            settings::view(form)
+        } else if let Some(email) = &self.opened_email {
+            self.email_view(email, "‹ Back", Message::CloseEmail)
+        } else if let Some(dossier) = &self.prep {
+            prep::view(dossier)
+        } else if let Some(hits) = &self.search_results {
+            self.search_view(hits)
+        } else {
+            match self.panel {
+                Panel::Briefing => self.summary_view(shown),
+                Panel::Actions => actions::view(&self.actions, &self.muted, today()),
+                Panel::People => contacts::view(
+                    &self.contacts,
+                    self.contact_form.as_ref().map(|(_, contact)| contact),
+                ),
+                Panel::Projects => projects::view(&self.projects, &self.history),
+            }
         };
+        let source = (self.palette.is_none()
+            && self.settings.is_none()
+            && self.opened_email.is_none()
+            && self.prep.is_none()
+            && self.search_results.is_none()
+            && self.panel == Panel::Briefing)
+            .then_some(self.source.as_ref())
+            .flatten()
+            .map(|email| {
+                scrollable(
+                    container(self.email_view(email, "Close", Message::CloseSource))
+                        .padding(16)
+                        .style(|theme: &Theme| container::Style {
+                            background: Some(theme.palette().text.scale_alpha(0.03).into()),
+                            border: Border {
+                                radius: iced::border::Radius::from(6.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        }),
+                )
+                .width(Length::FillPortion(2))
+                .height(Length::Fill)
+            });
+
+        let notice = self.notice.as_ref().map(|notice| {
+            row![
+                text(notice)
+                    .font(fonts::body())
+                    .size(fonts::scaled(12))
+                    .style(warning_text)
+                    .width(Length::Fill),
+                picker_button("Dismiss", Some(Message::DismissNotice)),
+            ]
+            .spacing(6)
+            .align_y(iced::Alignment::Center)
+        });
+
+        let vault_locked = self.settings.is_none()
+            && !secrets::is_unlocked()
+            && self
+                .config
+                .resolve(&self.profile)
+                .is_ok_and(|profile| profile.uses_vault() || smtp::uses_vault());
+        let unlock = vault_locked.then(|| {
+            row![
+                text_input("Vault passphrase", &self.vault_passphrase)
+                    .font(fonts::body())
+                    .size(fonts::scaled(13))
+                    .secure(true)
+                    .on_input(Message::VaultPassphraseChanged)
+                    .on_submit(Message::UnlockVault),
+                picker_button("Unlock", Some(Message::UnlockVault)),
+            ]
+            .spacing(6)
+            .align_y(iced::Alignment::Center)
+        });
+
+        let export_menu = (self.export_menu && shown.is_some()).then(|| {
+            row(export::Format::ALL
+                .into_iter()
+                .map(|format| picker_button(format.label(), Some(Message::Export(format))).into()))
+            .spacing(6)
+        });
 
-        let mut content = column![
-            scrollable(
-                column![text(&self.summary).font(BODY_FONT)].padding(Padding {
+        // A narrow window shows the cited email in place of the briefing rather than beside it
+        let compact = self.is_compact();
+        let briefing = (!compact || source.is_none()).then(|| {
+            scrollable(column![body].padding(if compact {
+                Padding::new(8.0)
+            } else {
+                Padding {
                     top: 80.0,
                     right: 40.0,
                     bottom: 40.0,
                     left: 80.0,
-                })
-            )
-            .height(Length::Fill),
-            text(&self.last_updated)
-                .font(BODY_FONT)
-                .size(14)
-                .color(iced::Color::from_rgb8(200, 200, 200)),
-            button(
-                text("⭮ Refresh")
-                    .font(BODY_FONT)
-                    .size(12)
-                    .color(iced::Color::from_rgb8(156, 156, 156))
-            )
-            .on_press(Message::RefreshPressed)
-            .style(|_theme, _state| {
-                button::Style {
-                    background: Some(iced::Color::from_rgb8(30, 30, 30).into()),
-                    ..Default::default()
                 }
-            }),
+            }))
+            .width(Length::FillPortion(3))
+            .height(Length::Fill)
+        });
+
+        let content = column![
+            notice,
+            unlock,
+            search_bar,
+            degraded_notice,
+            row![briefing, source].spacing(10).height(Length::Fill),
+            text(&self.last_updated)
+                .font(fonts::body())
+                .size(fonts::scaled(14)),
+            text(usage_line)
+                .font(fonts::body())
+                .size(fonts::scaled(11))
+                .style(hint_text),
+            row![
+                button(
+                    text("⭮ Refresh")
+                        .font(fonts::body())
+                        .size(fonts::scaled(12))
+                        .style(secondary_text)
+                )
+                .on_press_maybe(self.job.is_none().then_some(Message::RefreshPressed))
+                .style(|theme: &Theme, _state| {
+                    button::Style {
+                        background: Some(theme.palette().background.into()),
+                        ..Default::default()
+                    }
+                }),
+                pick_list(
+                    Kind::ALL,
+                    Some(self.refresh_kind),
+                    Message::RefreshKindSelected
+                )
+                .font(fonts::body())
+                .text_size(fonts::scaled(11)),
+            ]
+            .spacing(6)
+            .align_y(iced::Alignment::Center),
+            row![
+                picker_button(
+                    "Regenerate",
+                    self.job.is_none().then_some(Message::RegeneratePressed)
+                ),
+                self.job
+                    .is_some()
+                    .then(|| picker_button("Cancel", Some(Message::CancelRefresh))),
+                picker_button(
+                    "Export ▾",
+                    shown.is_some().then_some(Message::ToggleExportMenu)
+                ),
+                row(Panel::OTHERS.into_iter().map(|panel| {
+                    let label = if self.panel == panel {
+                        Panel::Briefing.label()
+                    } else {
+                        panel.label()
+                    };
+                    picker_button(label, Some(Message::TogglePanel(panel))).into()
+                }))
+                .spacing(6),
+                picker_button(
+                    "Settings",
+                    self.settings.is_none().then_some(Message::OpenSettings)
+                ),
+                (self.config.profiles.len() > 1).then(|| {
+                    pick_list(
+                        self.config.profile_names(),
+                        Some(self.profile.clone()),
+                        Message::ProfileSelected,
+                    )
+                    .font(fonts::body())
+                    .text_size(fonts::scaled(11))
+                }),
+            ]
+            .spacing(6)
+            .wrap(),
+            export_menu,
             row![
                 btn_previous
-                    .style(|_theme, _state| {
+                    .style(|theme: &Theme, _state| {
                         button::Style {
                             border: Border {
                                 radius: iced::border::Radius {
@@ -185,17 +1756,16 @@ impl Tits {
                                     bottom_right: 0.0,
                                 },
                                 width: 0.5,
-                                color: iced::Color::from_rgba(1.0, 1.0, 1.0, 0.1),
-                                ..Default::default()
+                                color: theme.palette().text.scale_alpha(0.1),
                             },
-                            background: Some(iced::Color::from_rgba(1.0, 1.0, 1.0, 0.05).into()),
-                            text_color: iced::Color::from_rgba(1.0, 1.0, 1.0, 0.5),
+                            background: Some(theme.palette().text.scale_alpha(0.05).into()),
+                            text_color: theme.palette().text.scale_alpha(0.5),
                             ..Default::default()
                         }
                     })
                     .padding(iced::Padding::from([4.0, 24.0])),
                 btn_next
-                    .style(|_theme, _state| {
+                    .style(|theme: &Theme, _state| {
                         button::Style {
                             border: Border {
                                 radius: iced::border::Radius {
@@ -205,19 +1775,23 @@ impl Tits {
                                     bottom_right: 6.0,
                                 },
                                 width: 0.5,
-                                color: iced::Color::from_rgba(1.0, 1.0, 1.0, 0.1),
-                                ..Default::default()
+                                color: theme.palette().text.scale_alpha(0.1),
                             },
-                            background: Some(iced::Color::from_rgba(1.0, 1.0, 1.0, 0.05).into()),
-                            text_color: iced::Color::from_rgba(1.0, 1.0, 1.0, 0.5),
+                            background: Some(theme.palette().text.scale_alpha(0.05).into()),
+                            text_color: theme.palette().text.scale_alpha(0.5),
                             ..Default::default()
                         }
                     })
                     .padding(iced::Padding::from([4.0, 24.0]))
-            ]
+            ],
+            date_picker
         ]
-        .max_width(800)
-        .spacing(10)
+        .max_width(match (compact, self.source.is_some()) {
+            (true, _) => f32::INFINITY,
+            (false, true) => 1200.0,
+            (false, false) => 800.0,
+        })
+        .spacing(if compact { 6 } else { 10 })
         .align_x(iced::Alignment::Center);
 
         container(content)
@@ -225,9 +1799,99 @@ impl Tits {
             .height(Length::Fill)
             .center_x(Length::Fill)
             .center_y(Length::Fill)
-            .padding(120)
-            .style(|_theme| container::Style {
-                background: Some(iced::Color::from_rgb8(30, 30, 30).into()),
+            .padding(if compact { 12 } else { 120 })
+            .style(|theme: &Theme| container::Style {
+                background: Some(theme.palette().background.into()),
+                border: Border {
+                    radius: iced::border::Radius::from(6.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .into()
+    }
+
+    fn palette_view(&self, palette: &Palette) -> Element<'_, Message> {
+        let entries = self.palette_entries();
+        let selected = palette
+            .selected
+            .min(entries.len().min(palette::SHOWN).saturating_sub(1));
+
+        let list = column(entries.into_iter().enumerate().take(palette::SHOWN).map(
+            |(index, entry)| {
+                let chosen = index == selected;
+                button(
+                    row![
+                        text(entry.label)
+                            .font(fonts::body())
+                            .size(fonts::scaled(13))
+                            .width(Length::Fill),
+                        text(entry.hint)
+                            .font(fonts::body())
+                            .size(fonts::scaled(11))
+                            .style(hint_text),
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                )
+                .on_press(Message::PaletteRun(index))
+                .width(Length::Fill)
+                .style(move |theme: &Theme, _state| button::Style {
+                    background: Some(
+                        theme
+                            .palette()
+                            .text
+                            .scale_alpha(if chosen { 0.08 } else { 0.02 })
+                            .into(),
+                    ),
+                    text_color: theme.palette().text,
+                    border: Border {
+                        radius: iced::border::Radius::from(6.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .into()
+            },
+        ))
+        .spacing(4);
+
+        column![
+            text_input("Type a command or a date", &palette.query)
+                .id(palette::INPUT)
+                .font(fonts::body())
+                .size(fonts::scaled(14))
+                .on_input(Message::PaletteChanged)
+                .on_submit(Message::PaletteSubmitted),
+            list,
+        ]
+        .spacing(10)
+        .into()
+    }
+
+    fn search_view<'a>(&self, hits: &'a [Hit]) -> Element<'a, Message> {
+        if hits.is_empty() {
+            return text("No matches")
+                .font(fonts::body())
+                .size(fonts::scaled(16))
+                .into();
+        }
+
+        column(hits.iter().map(|hit| {
+            button(
+                column![
+                    text(&hit.title).font(fonts::body()).size(fonts::scaled(13)),
+                    text(&hit.snippet)
+                        .font(fonts::body())
+                        .size(fonts::scaled(12))
+                        .style(secondary_text),
+                ]
+                .spacing(4),
+            )
+            .on_press(Message::OpenHit(hit.target.clone()))
+            .width(Length::Fill)
+            .style(|theme: &Theme, _state| button::Style {
+                background: Some(theme.palette().text.scale_alpha(0.03).into()),
                 border: Border {
                     radius: iced::border::Radius::from(6.0),
                     ..Default::default()
@@ -235,104 +1899,470 @@ impl Tits {
                 ..Default::default()
             })
             .into()
+        }))
+        .spacing(8)
+        .into()
+    }
+
+    // The cards, the meetings they mention and the briefing's action items
+    fn summary_view<'a>(&'a self, shown: Option<&'a Briefing>) -> Element<'a, Message> {
+        let Some(briefing) = shown else {
+            return text(&self.summary)
+                .font(fonts::body())
+                .size(fonts::scaled(16))
+                .into();
+        };
+
+        let cards = cards::view(&self.cards, &self.muted, today());
+
+        let meetings = (!self.meetings.is_empty()).then(|| prep::meetings_view(&self.meetings));
+
+        let summary = column![
+            cards,
+            meetings,
+            row![
+                small_button("Copy all", Message::CopyBriefing),
+                small_button("Copy as Markdown", Message::CopyMarkdown),
+            ]
+            .spacing(4),
+        ]
+        .spacing(12);
+        if briefing.action_items.is_empty() {
+            return summary.into();
+        }
+
+        column![
+            summary,
+            text(
+                match self
+                    .history
+                    .all()
+                    .iter()
+                    .rev()
+                    .find(|b| b.id < briefing.id && b.kind != Kind::Weekly)
+                {
+                    Some(previous) => format!("Items since {}", previous.label()),
+                    None => String::from("Items"),
+                }
+            )
+            .font(fonts::body())
+            .size(fonts::scaled(13))
+            .style(hint_text),
+            actions::found_view(&briefing.action_items),
+        ]
+        .spacing(16)
+        .into()
+    }
+
+    fn email_view<'a>(
+        &self,
+        email: &'a Email,
+        close_label: &'static str,
+        close: Message,
+    ) -> Element<'a, Message> {
+        let header = |label: &'static str, value: &'a str| {
+            text(format!("{}: {}", label, value))
+                .font(fonts::body())
+                .size(fonts::scaled(12))
+                .style(secondary_text)
+        };
+
+        let attachments = (!email.attachments.is_empty()).then(|| {
+            column(email.attachments.iter().map(|attachment| {
+                text(format!("📎 {}", attachment.describe()))
+                    .font(fonts::body())
+                    .size(fonts::scaled(12))
+                    .style(secondary_text)
+                    .into()
+            }))
+            .spacing(2)
+        });
+
+        column![
+            button(
+                text(close_label)
+                    .font(fonts::body())
+                    .size(fonts::scaled(12))
+            )
+            .on_press(close)
+            .style(|theme: &Theme, _state| button::Style {
+                background: Some(theme.palette().background.into()),
+                text_color: theme.palette().text.scale_alpha(0.7),
+                ..Default::default()
+            }),
+            text(&email.subject)
+                .font(fonts::body())
+                .size(fonts::scaled(18)),
+            header("From", &email.from),
+            header("To", &email.to),
+            (!email.cc.is_empty()).then(|| header("Cc", &email.cc)),
+            header("Date", &email.date),
+            attachments,
+            text(mail::clean_body(&email.body))
+                .font(fonts::body())
+                .size(fonts::scaled(16)),
+        ]
+        .spacing(6)
+        .into()
     }
 
-    fn get_state_file() -> PathBuf {
+    fn get_state_file() -> Result<PathBuf, String> {
         let project_dirs = ProjectDirs::from("com", "Apex", "tit-babbler")
-            .expect("Could not determine project directory");
+            .ok_or("Could not determine project directory")?;
 
         let config_dir = project_dirs.config_dir();
 
         if !config_dir.exists() {
-            fs::create_dir_all(config_dir).expect("Failed to create config directory");
+            fs::create_dir_all(config_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
         }
 
-        config_dir.join("state.json")
+        Ok(config_dir.join("state.json"))
+    }
+
+    fn save(&mut self) {
+        if let Err(e) = self.write_state() {
+            self.notice = Some(format!("Could not save state: {}", e));
+        }
+    }
+
+    fn write_state(&self) -> Result<(), String> {
+        let path = Self::get_state_file()?;
+        let state =
+            serde_json::to_value(self).map_err(|e| format!("Failed to serialize state: {}", e))?;
+        let json = serde_json::to_string(&persist::wrap(state))
+            .map_err(|e| format!("Failed to serialize state: {}", e))?;
+
+        // Only a file we can still read is worth keeping as the backup
+        if let Ok(content) = fs::read_to_string(&path)
+            && Self::parse(&content).is_ok()
+        {
+            fs::write(persist::backup_path(&path), content)
+                .map_err(|e| format!("Failed to back up state file: {}", e))?;
+        }
+
+        persist::write_atomic(&path, json.as_bytes())
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let state = persist::unwrap(content)?;
+
+        serde_json::from_value(state)
+            .map_err(|e| format!("State file does not match the expected layout: {}", e))
+    }
+
+    // Falls back to the backup, then to defaults, and says so instead of losing data silently
+    fn recover() -> (Self, Option<String>) {
+        let path = match Self::get_state_file() {
+            Ok(path) => path,
+            Err(e) => return (Self::default(), Some(e)),
+        };
+
+        let (state, notice) = persist::recover(&path, Self::parse);
+
+        (state.unwrap_or_default(), notice)
     }
 
-    fn save(&self) {
-        let path = Self::get_state_file();
-        let json = serde_json::to_string(self).expect("Failed to serialize state");
+    fn load(profile: Option<String>) -> Self {
+        let (mut state, notice) = Self::recover();
+        state.notice = notice;
 
-        fs::write(path, json).expect("Failed to write state file");
+        state.history = History::load();
+        state.import_legacy_briefings();
+
+        state.index = Index::build(state.history.all(), &archive::load_all());
+        if let Some(id) = state.selected {
+            state.show_briefing(id);
+        }
+
+        match Config::read() {
+            Ok(config) => state.config = config,
+            Err(e) => {
+                state.notice = Some(format!("Ignoring settings: {}", e));
+                state.config = Config::load();
+            }
+        }
+        fonts::apply(&state.config);
+        match state.config.schedules() {
+            Ok(schedules) => state.schedules = schedules,
+            Err(e) => state.notice = Some(format!("Ignoring schedule: {}", e)),
+        }
+
+        state.profile = match profile {
+            Some(name) if state.config.profiles.contains_key(&name) => name,
+            Some(name) => {
+                state.notice = Some(format!("No profile named \"{}\"", name));
+                state.config.active_name()
+            }
+            None => state.config.active_name(),
+        };
+
+        // First run without a .env, or one that is missing something
+        if !state.config.problems(&state.profile).is_empty() {
+            state.settings = Some(settings::Form::new(&state.config, &state.profile));
+        }
+
+        state
     }
 
-    fn load() -> Self {
-        let path = Self::get_state_file();
+    // Moves briefings from the pre-history layout into the history store
+    fn import_legacy_briefings(&mut self) {
+        let legacy = std::mem::take(&mut self.legacy_briefings);
+        let imported = !legacy.is_empty();
 
-        if let Ok(content) = fs::read_to_string(path) {
-            if let Ok(state) = serde_json::from_str(&content) {
-                return state;
+        for text in legacy {
+            let now = Local::now();
+            let briefing = Briefing {
+                id: 0,
+                kind: Kind::Morning,
+                created_at: now,
+                window_start: now,
+                window_end: now,
+                source_email_ids: Vec::new(),
+                model: String::from("unknown"),
+                usage: None,
+                text,
+                cached: false,
+                degraded: None,
+                action_items: Vec::new(),
+                projects: Vec::new(),
+                meetings: None,
+            };
+            if let Err(e) = self.history.push(briefing) {
+                self.notice = Some(format!("Could not import an earlier briefing: {}", e));
             }
         }
 
-        Self::default()
+        if imported {
+            self.selected = self.history.latest(Filter::All).map(|b| b.id);
+            self.save();
+        }
+    }
+
+    // Checks the schedule right away so a run missed while closed is caught up on startup
+    fn boot(profile: Option<String>) -> (Self, Task<Message>) {
+        (Self::load(profile), Task::done(Message::ScheduleTick))
+    }
+
+    // Previews the theme picked in the settings form before it is saved
+    fn theme(&self) -> Option<Theme> {
+        self.settings
+            .as_ref()
+            .map_or(self.config.theme, |form| form.config.theme)
+            .theme()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let mut subscriptions = vec![
+            iced::window::resize_events().map(|(_, size)| Message::WindowResized(size.width)),
+            iced::event::listen_with(palette::shortcut),
+        ];
+        if !self.schedules.iter().all(|(_, rules)| rules.is_empty()) {
+            subscriptions.push(
+                iced::time::every(std::time::Duration::from_secs(30))
+                    .map(|_| Message::ScheduleTick),
+            );
+        }
+
+        Subscription::batch(subscriptions)
+    }
+
+    // Previews the layout picked in the settings form like the theme
+    fn is_compact(&self) -> bool {
+        self.settings
+            .as_ref()
+            .map_or(self.config.layout, |form| form.config.layout)
+            .is_compact(self.window_width)
+    }
+}
+
+pub async fn refresh_inbox(
+    profile: Profile,
+    kind: Kind,
+    window_hours: u32,
+    force: bool,
+    context: prompt::Context,
+    muted: mute::Rules,
+    progress: Progress,
+) -> Result<Briefing, String> {
+    let window_end = Local::now();
+    let briefing = Briefing {
+        id: 0,
+        kind,
+        created_at: window_end,
+        window_start: window_end - Duration::hours(window_hours.into()),
+        window_end,
+        source_email_ids: Vec::new(),
+        model: profile.llm.model.clone(),
+        usage: None,
+        text: String::new(),
+        cached: false,
+        degraded: None,
+        action_items: Vec::new(),
+        projects: Vec::new(),
+        meetings: None,
+    };
+
+    if kind == Kind::Weekly {
+        return weekly_digest(&profile, briefing, force, &context, &progress).await;
+    }
+
+    let mut briefing =
+        summarize_inbox(&profile, briefing, force, &context, &muted, &progress).await?;
+
+    // Marked against the previous briefing whether or not the model was asked for changes only
+    if let Some(previous) = &context.previous {
+        actions::mark_changes(&mut briefing.action_items, &previous.items);
     }
+
+    Ok(briefing)
+}
+
+// The model writes the briefing, then lists action items, projects and meetings in that order
+fn read_response(briefing: &mut Briefing, response: &str, emails: &[Email]) {
+    let (response, meetings) = prep::split_response(response, emails);
+    let (response, updates) = projects::split_response(&response);
+    (briefing.text, briefing.action_items) = actions::split_response(&response, emails);
+    briefing.projects = updates;
+    briefing.meetings = meetings;
 }
 
-pub async fn refresh_inbox() -> Result<String, String> {
-    let emails = mail::fetch_emails().await?;
-    let formatted_emails = mail::email_formatter(emails);
+async fn summarize_inbox(
+    profile: &Profile,
+    mut briefing: Briefing,
+    force: bool,
+    context: &prompt::Context,
+    muted: &mute::Rules,
+    progress: &Progress,
+) -> Result<Briefing, String> {
+    // IMAP searches by day, so the window is cut to the hour after fetching
+    let since = briefing.window_start.date_naive();
+    let mut emails = mail::fetch_emails(&profile.account, since, progress).await?;
+    if let Err(e) = archive::store(&emails) {
+        eprintln!("{}", e);
+    }
+    emails.retain(|email| {
+        mail::received_at(email).is_none_or(|received| received >= briefing.window_start)
+    });
+    muted.apply(&mut emails, briefing.window_end.date_naive());
+
+    let model = profile.llm.model.clone();
+    let template = profile.template(briefing.kind);
+
+    briefing.source_email_ids = emails.iter().map(|email| email.id.clone()).collect();
+
+    let formatted_emails = mail::email_formatter(&emails);
 
     if formatted_emails.is_empty() {
-        return Ok(String::new());
-    }
-
-    let response = ai::generate_response(format!(
-        r#"<system_capability>
-    You are an elite Executive Assistant and Chief of Staff. Your goal is to synthesize high-volume information into calm, actionable intelligence. You value clarity, brevity, and narrative flow over lists and formatting.
-    </system_capability>
-
-    <strict_authority_protocol>
-    ### FORMATTING CONSTANTS - READ CAREFULLY
-    1.  **PLAIN TEXT ONLY**: You are STRICTLY FORBIDDEN from using Markdown.
-        -   NO bolding (**text**).
-        -   NO italics (*text*).
-        -   NO headers (###).
-        -   NO bullet points (-) or numbered lists (1.).
-    2.  **PARAGRAPHS**: Content must be delivered in smooth, readable paragraphs.
-    3.  **FAILURE CONDITION**: If the output contains a single asterisk or bullet point, the response is considered a failure.
-    </strict_authority_protocol>
-
-    <processing_logic>
-    Step 1: **FILTER**. Aggressively discard trivial emails (newsletters, receipts, notifications, "checking in" emails) unless they contain a direct blocker or urgent deadline - USERS DO NOT WANT SPAM IN THEIR BREIFING.
-    Step 2: **EXTRACT**. Identify:
-        -   Upcoming meetings (Who, When, Context).
-        -   Direct questions asked of the user.
-        -   Urgent blockers or red flags.
-        -   Status updates on active projects.
-    Step 3: **SYNTHESIZE**. Draft a briefing using a calm, professional tone.
-        -   Start with "Good day, Apex.".
-        -   Group related items into paragraphs (e.g., Meeting context in para 1, Project blockers in para 2).
-        -   End with a strategic next step if applicable.
-    </processing_logic>
-
-    <few_shot_examples>
-    Input: [Raw Emails containing: 1. Newsletter from Substack, 2. Meeting reminder for ScyAI at 7pm, 3. Email from Bernhard about missing login screen, 4. WhatsApp group chatter about QR codes vs Roam, 5. SuperWhisper team update on landing page.]
-
-    Output:
-    Good day, Apex.
-
-    You have a meeting coming up in about 3.5 hours - ScyAI x UI/UX Sync at 7pm with Bernhard. Before that call, you should know that Bernhard flagged a missing login screen in the ScyAI Design group. They're implementing one-time passwords for first login, but users need to change their password immediately after. He's looking for that additional screen to be designed.
-
-    Also in your WhatsApp groups, someone from the Visualizations/Branding Co is asking about QR codes and whether you prefer communication through that chat or Roam. Julian pushed back hard on QR codes, but the original question about your preferred communication method is still hanging.
-
-    Your SuperWhisper team has been busy - they've got a new landing page ready for feedback. The conversation shows they've been iterating on animations and user experience, with some good discussion about making the demo less interactive during autoplay.
-
-    I'd prioritize prepping for the ScyAI meeting by reviewing that missing login screen requirement. The day looks manageable with just the one evening meeting.
-    </few_shot_examples>
-
-    <task>
-    Summarize the following raw emails into a morning briefing following the strict formatting protocols above.
-
-    EMAILS:
-    {}
-    </task>"#,
-        formatted_emails
-    ))
-    .await?;
-
-    Ok(response)
+        return Ok(briefing);
+    }
+
+    let people = context.contacts.relevant(&emails);
+    let prompt = prompt::briefing_prompt(template, &formatted_emails, &people, context);
+    let baseline = context
+        .previous
+        .as_ref()
+        .filter(|_| context.delta)
+        .map(|previous| previous.id);
+    let cache_key = cache::key(&emails, template, &model, baseline);
+
+    if !force && let Some(cached) = cache::load(&cache_key) {
+        read_response(&mut briefing, &cached.text, &emails);
+        briefing.cached = true;
+        return Ok(briefing);
+    }
+
+    progress.report(Stage::Summarizing(emails.len()));
+    let (text, usage) = match ai::generate_response(&profile.llm, prompt, progress).await {
+        Ok(response) => response,
+        Err(error) => {
+            briefing.text = fallback::summarize(&emails, &profile.account, &context.contacts);
+            briefing.action_items =
+                fallback::action_items(&emails, &profile.account, &context.contacts);
+            briefing.degraded = Some(error);
+            return Ok(briefing);
+        }
+    };
+
+    cache::store(
+        &cache_key,
+        &cache::CachedResponse {
+            text: text.clone(),
+            model: model.clone(),
+            usage,
+            created_at: Local::now(),
+        },
+    );
+
+    read_response(&mut briefing, &text, &emails);
+    briefing.usage = Some(UsageRecord::new(&model, usage));
+
+    Ok(briefing)
+}
+
+// Built from the briefings already stored rather than from mail
+async fn weekly_digest(
+    profile: &Profile,
+    mut briefing: Briefing,
+    force: bool,
+    context: &prompt::Context,
+    progress: &Progress,
+) -> Result<Briefing, String> {
+    if context.briefings.is_empty() {
+        return Err(String::from(
+            "No briefings this week to build a digest from",
+        ));
+    }
+
+    let formatted_briefings = context
+        .briefings
+        .iter()
+        .map(|b| format!("{}\n{}", b.title(), cite::strip(&b.text)))
+        .collect::<Vec<String>>()
+        .join("\n\n---\n\n");
+
+    let model = profile.llm.model.clone();
+    let template = profile.template(Kind::Weekly);
+    let prompt = prompt::weekly_prompt(template, &formatted_briefings, context);
+    // The briefings digested stand in for the mail
+    let cache_key = cache::key(
+        &[],
+        &format!("{}\n{}", template, formatted_briefings),
+        &model,
+        None,
+    );
+
+    if !force && let Some(cached) = cache::load(&cache_key) {
+        briefing.text = cached.text.trim_end().to_string();
+        briefing.cached = true;
+        return Ok(briefing);
+    }
+
+    progress.report(Stage::Digesting(context.briefings.len()));
+    let (text, usage) = match ai::generate_response(&profile.llm, prompt, progress).await {
+        Ok(response) => response,
+        Err(error) => {
+            briefing.text =
+                fallback::weekly(&context.briefings, &context.open_items, &context.done_items);
+            briefing.degraded = Some(error);
+            return Ok(briefing);
+        }
+    };
+
+    cache::store(
+        &cache_key,
+        &cache::CachedResponse {
+            text: text.clone(),
+            model: model.clone(),
+            usage,
+            created_at: Local::now(),
+        },
+    );
+
+    briefing.text = text.trim_end().to_string();
+    briefing.usage = Some(UsageRecord::new(&model, usage));
+
+    Ok(briefing)
 }
 
 fn load_icon() -> Option<iced::window::Icon> {
@@ -348,16 +2378,33 @@ fn load_icon() -> Option<iced::window::Icon> {
 
 pub fn main() -> iced::Result {
     dotenv().ok();
+
+    let cli = cli::Cli::parse();
+    if let Some(command) = cli.command {
+        if let Err(e) = cli::run(command, cli.profile) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     println!("Key found!");
 
-    iced::application(Tits::load, Tits::update, Tits::view)
-        .title(|_: &Tits| String::from("Tit-Babbler"))
-        .theme(|_: &Tits| Theme::Dark)
-        .window(iced::window::Settings {
-            decorations: true,
-            transparent: false,
-            icon: load_icon(),
-            ..Default::default()
-        })
-        .run()
+    let profile = cli.profile;
+    iced::application(
+        move || Tits::boot(profile.clone()),
+        Tits::update,
+        Tits::view,
+    )
+    .title(|_: &Tits| String::from("Tit-Babbler"))
+    .subscription(Tits::subscription)
+    .font(fonts::BUNDLED)
+    .theme(Tits::theme)
+    .window(iced::window::Settings {
+        decorations: true,
+        transparent: false,
+        icon: load_icon(),
+        ..Default::default()
+    })
+    .run()
 }
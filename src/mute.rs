@@ -0,0 +1,143 @@
+use crate::fallback;
+use crate::mail::Email;
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+// Threads, senders and single emails kept out of briefings, for good or until a date. Mail a
+// rule matches is still archived and searchable, it just never reaches the model.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    // Subject without reply and forward prefixes, lowercased
+    Thread(String),
+    // Lowercased address
+    Sender(String),
+    Email(String),
+}
+
+impl Target {
+    pub fn thread(email: &Email) -> Self {
+        Target::Thread(thread_key(&email.subject))
+    }
+
+    pub fn sender(email: &Email) -> Self {
+        Target::Sender(fallback::address(&email.from))
+    }
+
+    fn matches(&self, email: &Email) -> bool {
+        match self {
+            Target::Thread(key) => *key == thread_key(&email.subject),
+            Target::Sender(address) => *address == fallback::address(&email.from),
+            Target::Email(id) => *id == email.id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reason {
+    Muted,
+    Snoozed,
+    Handled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub target: Target,
+    pub reason: Reason,
+    // Subject or sender as shown when the rule was made
+    pub label: String,
+    pub created_at: DateTime<Local>,
+    // Last day the rule holds; none for good
+    pub until: Option<NaiveDate>,
+}
+
+impl Rule {
+    pub fn is_active(&self, today: NaiveDate) -> bool {
+        self.until.is_none_or(|until| until >= today)
+    }
+
+    pub fn describe(&self) -> String {
+        let what = match &self.target {
+            Target::Thread(_) => format!("Thread \"{}\"", self.label),
+            Target::Sender(_) => format!("Sender {}", self.label),
+            Target::Email(_) => format!("Email \"{}\"", self.label),
+        };
+
+        match (self.reason, self.until) {
+            (Reason::Snoozed, Some(until)) => {
+                format!("{} · snoozed until {}", what, until.format("%b %-d"))
+            }
+            (Reason::Handled, _) => format!("{} · handled", what),
+            _ => format!("{} · muted", what),
+        }
+    }
+}
+
+pub fn thread_key(subject: &str) -> String {
+    let mut subject = subject.trim();
+    loop {
+        let lower = subject.to_lowercase();
+        let Some(prefix) = ["re:", "fwd:", "fw:", "aw:"]
+            .into_iter()
+            .find(|prefix| lower.starts_with(prefix))
+        else {
+            break;
+        };
+        subject = subject[prefix.len()..].trim_start();
+    }
+
+    subject
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    pub fn all(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    // A new rule for a target replaces the one it already has
+    pub fn add(&mut self, target: Target, reason: Reason, label: &str, until: Option<NaiveDate>) {
+        self.remove(&target);
+        self.rules.push(Rule {
+            target,
+            reason,
+            label: label.trim().to_string(),
+            created_at: Local::now(),
+            until,
+        });
+    }
+
+    pub fn remove(&mut self, target: &Target) {
+        self.rules.retain(|rule| rule.target != *target);
+    }
+
+    // Lifts every rule that keeps this email out
+    pub fn release(&mut self, email: &Email) {
+        self.rules.retain(|rule| !rule.target.matches(email));
+    }
+
+    pub fn prune(&mut self, today: NaiveDate) {
+        self.rules.retain(|rule| rule.is_active(today));
+    }
+
+    pub fn matching(&self, email: &Email, today: NaiveDate) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.is_active(today) && rule.target.matches(email))
+    }
+
+    pub fn apply(&self, emails: &mut Vec<Email>, today: NaiveDate) {
+        emails.retain(|email| self.matching(email, today).is_none());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::Message;
+use chrono::{Datelike, Duration, Local, NaiveDate, Weekday};
+use iced::event::{self, Event};
+use iced::keyboard::{self, Key, key::Named};
+use iced::window;
+
+// Keyboard bindings, and the Ctrl+K palette that reaches every action by typing part of its name
+
+pub const INPUT: &str = "palette";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortcut {
+    Palette,
+    Refresh,
+    Previous,
+    Next,
+    Search,
+    Copy,
+    Settings,
+    Cancel,
+    Up,
+    Down,
+}
+
+impl Shortcut {
+    // Shown next to the action in the palette
+    pub fn keys(self) -> &'static str {
+        match self {
+            Shortcut::Palette => "Ctrl+K",
+            Shortcut::Refresh => "Ctrl+R",
+            Shortcut::Previous => "←",
+            Shortcut::Next => "→",
+            Shortcut::Search => "Ctrl+F",
+            Shortcut::Copy => "Ctrl+Shift+C",
+            Shortcut::Settings => "Ctrl+,",
+            Shortcut::Cancel => "Esc",
+            Shortcut::Up => "↑",
+            Shortcut::Down => "↓",
+        }
+    }
+}
+
+// Bare keys only count when no text field took them, so typing a slash or moving the cursor
+// still works; combinations with Ctrl work everywhere
+pub fn shortcut(event: Event, status: event::Status, _window: window::Id) -> Option<Message> {
+    let Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) = event else {
+        return None;
+    };
+    let free = status == event::Status::Ignored;
+
+    let shortcut = match key.as_ref() {
+        Key::Character(c) if modifiers.command() => match c.to_lowercase().as_str() {
+            "k" => Shortcut::Palette,
+            "r" => Shortcut::Refresh,
+            "f" => Shortcut::Search,
+            "c" if modifiers.shift() => Shortcut::Copy,
+            "," => Shortcut::Settings,
+            _ => return None,
+        },
+        Key::Character("/") if free => Shortcut::Search,
+        Key::Named(Named::F5) => Shortcut::Refresh,
+        Key::Named(Named::Escape) => Shortcut::Cancel,
+        Key::Named(Named::ArrowLeft) if free || modifiers.alt() => Shortcut::Previous,
+        Key::Named(Named::ArrowRight) if free || modifiers.alt() => Shortcut::Next,
+        Key::Named(Named::ArrowUp) => Shortcut::Up,
+        Key::Named(Named::ArrowDown) => Shortcut::Down,
+        _ => return None,
+    };
+
+    Some(Message::Shortcut(shortcut))
+}
+
+// Entries listed at once; the selection never moves past them
+pub const SHOWN: usize = 12;
+
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    pub query: String,
+    pub selected: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub label: String,
+    pub hint: String,
+    pub message: Message,
+}
+
+impl Entry {
+    pub fn new(label: impl Into<String>, hint: impl Into<String>, message: Message) -> Self {
+        Self {
+            label: label.into(),
+            hint: hint.into(),
+            message,
+        }
+    }
+}
+
+// Every query character must appear in order. Runs of adjacent matches and matches at the start
+// of a word score higher, and shorter labels win ties.
+fn score(query: &str, label: &str) -> Option<i32> {
+    let label = label.to_lowercase().chars().collect::<Vec<char>>();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous = None;
+
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + label[position..].iter().position(|&c| c == wanted)?;
+
+        score += 1;
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 5;
+        }
+        if found == 0 || !label[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        previous = Some(found);
+        position = found + 1;
+    }
+
+    Some(score * 100 - label.len() as i32)
+}
+
+// Best matches first; an empty query keeps the given order
+pub fn filter(query: &str, entries: Vec<Entry>) -> Vec<Entry> {
+    if query.trim().is_empty() {
+        return entries;
+    }
+
+    let mut scored = entries
+        .into_iter()
+        .filter_map(|entry| score(query, &entry.label).map(|score| (score, entry)))
+        .collect::<Vec<(i32, Entry)>>();
+    scored.sort_by_key(|(score, _)| -score);
+
+    scored.into_iter().map(|(_, entry)| entry).collect()
+}
+
+// A typed date to jump to: today, yesterday, 2025-01-31, 1/31, or Jan 31 (this year, or last
+// year if that is still ahead)
+pub fn date(query: &str) -> Option<NaiveDate> {
+    typed_date(query, false)
+}
+
+// A typed date to snooze until: tomorrow, a weekday, 2025-01-31, 1/31, or Jan 31 (this year, or
+// next year if that has passed)
+pub fn date_ahead(query: &str) -> Option<NaiveDate> {
+    typed_date(query, true)
+}
+
+fn typed_date(query: &str, ahead: bool) -> Option<NaiveDate> {
+    let query = query.trim().to_lowercase();
+    let today = Local::now().date_naive();
+
+    match query.as_str() {
+        "today" => return Some(today),
+        "yesterday" if !ahead => return Some(today - Duration::days(1)),
+        "tomorrow" if ahead => return Some(today + Duration::days(1)),
+        _ => {}
+    }
+    if ahead && let Ok(weekday) = query.parse::<Weekday>() {
+        return (1..=7)
+            .map(|days| today + Duration::days(days))
+            .find(|date| date.weekday() == weekday);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&query, "%Y-%m-%d") {
+        return Some(date);
+    }
+
+    let this_year = format!("{} {}", query, today.year());
+    let date = ["%m/%d %Y", "%b %d %Y", "%B %d %Y", "%d %b %Y", "%d %B %Y"]
+        .into_iter()
+        .find_map(|format| NaiveDate::parse_from_str(&this_year, format).ok())?;
+
+    match (ahead, date > today, date < today) {
+        (false, true, _) => date.with_year(date.year() - 1),
+        (true, _, true) => date.with_year(date.year() + 1),
+        _ => Some(date),
+    }
+}
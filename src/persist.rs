@@ -0,0 +1,306 @@
+use chrono::Local;
+use serde_json::{Value, json};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const STATE_VERSION: u64 = 2;
+
+// Migration N upgrades a version N document to version N + 1
+const MIGRATIONS: [fn(Value) -> Result<Value, String>; 2] = [v0_to_v1, v1_to_v2];
+
+// Writes to a sibling temp file and renames it over the target, so readers only ever see
+// the old or the new contents, never a partial write
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    write(path, contents, false)
+}
+
+// Like write_atomic, for files holding credentials: only the owner can ever read them
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    write(path, contents, true)
+}
+
+fn write(path: &Path, contents: &[u8], private: bool) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&tmp)
+        .map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+
+    // The mode only applies to a new file, not to one left behind by an interrupted write
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict {}: {}", tmp.display(), e))?;
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+
+    file.write_all(contents)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+
+    // Persist the rename itself; not supported on every platform, so best effort
+    if let Some(parent) = path.parent()
+        && let Ok(dir) = fs::File::open(parent)
+    {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    path.with_extension("json.bak")
+}
+
+// Moves an unreadable file aside so a later save cannot overwrite it
+pub fn quarantine(path: &Path) -> Option<PathBuf> {
+    let target = path.with_extension(format!(
+        "json.unreadable-{}",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    fs::rename(path, &target).ok().map(|_| target)
+}
+
+// Reads a state file, moving it aside and falling back to its backup when it cannot be parsed.
+// None when there is nothing usable, with a notice whenever the user should hear about it.
+pub fn recover<T>(
+    path: &Path,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> (Option<T>, Option<String>) {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (None, None),
+        Err(e) => return (None, Some(format!("Could not read state file: {}", e))),
+    };
+
+    let error = match parse(&content) {
+        Ok(state) => return (Some(state), None),
+        Err(e) => e,
+    };
+
+    let kept = quarantine(path)
+        .map(|kept| format!(" The unreadable file was kept at {}.", kept.display()))
+        .unwrap_or_default();
+
+    match fs::read_to_string(backup_path(path))
+        .ok()
+        .and_then(|content| parse(&content).ok())
+    {
+        Some(state) => (
+            Some(state),
+            Some(format!("{}. Restored the last good backup.{}", error, kept)),
+        ),
+        None => (
+            None,
+            Some(format!(
+                "{}. No usable backup was found, starting fresh.{}",
+                error, kept
+            )),
+        ),
+    }
+}
+
+pub fn wrap(state: Value) -> Value {
+    json!({ "version": STATE_VERSION, "state": state })
+}
+
+// Accepts both the versioned envelope and the unversioned layout used before it
+pub fn unwrap(content: &str) -> Result<Value, String> {
+    let document: Value =
+        serde_json::from_str(content).map_err(|e| format!("State file is corrupt: {}", e))?;
+
+    let (mut version, mut state) = match document {
+        Value::Object(mut map) if map.contains_key("version") && map.contains_key("state") => {
+            let version = map
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or("State file has an invalid version")?;
+            (version, map.remove("state").unwrap_or(Value::Null))
+        }
+        other => (0, other),
+    };
+
+    if version > STATE_VERSION {
+        return Err(format!(
+            "State file was written by a newer version (schema {}, this build supports {})",
+            version, STATE_VERSION
+        ));
+    }
+
+    while version < STATE_VERSION {
+        state = MIGRATIONS[version as usize](state)
+            .map_err(|e| format!("Failed to migrate state from schema {}: {}", version, e))?;
+        version += 1;
+    }
+
+    Ok(state)
+}
+
+// The two-slot briefing model; the slots are carried over so they can be imported into history
+fn v0_to_v1(state: Value) -> Result<Value, String> {
+    let Value::Object(mut map) = state else {
+        return Err(String::from("expected an object"));
+    };
+
+    let legacy = ["previous_briefing", "current_briefing"]
+        .iter()
+        .filter_map(|key| map.remove(*key))
+        .filter_map(|value| value.as_str().map(str::to_string))
+        .filter(|text| !text.trim().is_empty() && !text.starts_with("Error: "))
+        .collect::<Vec<String>>();
+
+    for key in [
+        "previous_update",
+        "update_time",
+        "active",
+        "previous_usage",
+        "current_usage",
+        "previous_degraded",
+        "current_degraded",
+    ] {
+        map.remove(key);
+    }
+
+    map.insert(String::from("legacy_briefings"), json!(legacy));
+
+    Ok(Value::Object(map))
+}
+
+// One schedule became one per briefing kind, and the single schedule was the morning one
+fn v1_to_v2(state: Value) -> Result<Value, String> {
+    let Value::Object(mut map) = state else {
+        return Err(String::from("expected an object"));
+    };
+
+    let runs = match map.remove("last_scheduled_run") {
+        Some(Value::Null) | None => json!({}),
+        Some(last_run) => json!({ "morning": last_run }),
+    };
+    map.insert(String::from("last_scheduled_runs"), runs);
+
+    Ok(Value::Object(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory per test, since tests run in parallel
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "tit-babbler-persist-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn unversioned_file_migrates_to_current() {
+        let content = r#"{
+            "current_briefing": "Good morning.",
+            "previous_briefing": "Error: timed out",
+            "update_time": "08:00",
+            "last_scheduled_run": "2025-01-06T08:00:00+01:00",
+            "selected": 3
+        }"#;
+
+        let state = unwrap(content).unwrap();
+
+        assert_eq!(state["legacy_briefings"], json!(["Good morning."]));
+        assert_eq!(
+            state["last_scheduled_runs"],
+            json!({ "morning": "2025-01-06T08:00:00+01:00" })
+        );
+        assert_eq!(state["selected"], json!(3));
+        assert!(state.get("current_briefing").is_none());
+        assert!(state.get("update_time").is_none());
+        assert!(state.get("last_scheduled_run").is_none());
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let state = json!({ "selected": 1, "last_scheduled_runs": {} });
+        let content = serde_json::to_string(&wrap(state.clone())).unwrap();
+
+        assert_eq!(unwrap(&content).unwrap(), state);
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let content = json!({ "version": STATE_VERSION + 1, "state": {} }).to_string();
+
+        let error = unwrap(&content).unwrap_err();
+        assert!(error.contains("newer version"), "{}", error);
+    }
+
+    #[test]
+    fn truncated_file_is_quarantined_and_backup_loaded() {
+        let dir = scratch("truncated");
+        let path = dir.join("state.json");
+        let good = serde_json::to_string(&wrap(json!({ "selected": 7 }))).unwrap();
+        fs::write(backup_path(&path), &good).unwrap();
+        fs::write(&path, &good[..good.len() / 2]).unwrap();
+
+        let (state, notice) = recover(&path, unwrap);
+
+        assert_eq!(state.unwrap()["selected"], json!(7));
+        assert!(notice.unwrap().contains("Restored the last good backup"));
+        assert!(!path.exists());
+        let kept = fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("state.json.unreadable-")
+            })
+            .count();
+        assert_eq!(kept, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_file_without_backup_starts_fresh() {
+        let dir = scratch("no-backup");
+        let path = dir.join("state.json");
+        fs::write(&path, "{\"version\": 2, \"sta").unwrap();
+
+        let (state, notice) = recover(&path, unwrap);
+
+        assert!(state.is_none());
+        assert!(notice.unwrap().contains("No usable backup"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_file_is_not_an_error() {
+        let dir = scratch("missing");
+
+        let (state, notice) = recover(&dir.join("state.json"), unwrap);
+
+        assert!(state.is_none());
+        assert!(notice.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,437 @@
+use crate::actions::{ActionItem, Tracker};
+use crate::archive;
+use crate::cite;
+use crate::contacts::Book;
+use crate::fallback::{self, Meeting};
+use crate::mail::{self, Email};
+use crate::mute;
+use crate::search::{Hit, Index, Target};
+use crate::{Message, fonts, secondary_text, section_heading, small_button, warning_text};
+use chrono::NaiveDate;
+use iced::widget::{button, column, row, text};
+use iced::{Border, Element, Length, Theme};
+use serde::Deserialize;
+use std::cmp::Reverse;
+
+// What the user needs before a meeting found in the mail: who is coming, the cached threads on
+// the same subject or with the same people, what is still open with them, and what earlier
+// briefings said. Gathered locally; only the note on top is asked of the model.
+
+const MAX_THREADS: usize = 8;
+const MAX_MENTIONS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct Attendee {
+    pub name: String,
+    pub address: String,
+    // From the contacts the user kept, if any
+    pub about: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dossier {
+    pub meeting: Meeting,
+    pub attendees: Vec<Attendee>,
+    // Newest first
+    pub threads: Vec<Email>,
+    pub questions: Vec<ActionItem>,
+    pub mentions: Vec<Hit>,
+    // The model's note, none while it is being written
+    pub note: Option<Result<String, String>>,
+}
+
+fn participants(email: &Email) -> Vec<String> {
+    std::iter::once(email.from.as_str())
+        .chain(email.to.split(','))
+        .chain(email.cc.split(','))
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn keywords(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 3)
+        .map(String::from)
+        .collect()
+}
+
+// At least half the meeting's subject words appear in the email's
+fn same_subject(topic: &[String], email: &Email) -> bool {
+    let subject = keywords(&email.subject);
+    let shared = topic.iter().filter(|word| subject.contains(word)).count();
+
+    shared > 0 && shared * 2 >= topic.len()
+}
+
+fn emails(hits: Vec<Hit>) -> impl Iterator<Item = Email> {
+    hits.into_iter()
+        .filter_map(|hit| match hit.target {
+            Target::Email(id) => archive::load(&id),
+            Target::Briefing(_) => None,
+        })
+        .take(MAX_THREADS * 2)
+}
+
+#[derive(Deserialize)]
+struct Listed {
+    title: String,
+    #[serde(default)]
+    when: String,
+    #[serde(default)]
+    organizer: String,
+    #[serde(default)]
+    email: String,
+}
+
+fn is_marker(line: &str) -> bool {
+    line.trim()
+        .trim_matches(|c: char| c == '*' || c == '#' || c == ':' || c.is_whitespace())
+        .eq_ignore_ascii_case("meetings")
+}
+
+// The model lists meetings after a line reading MEETINGS, one JSON object per line, following
+// its projects. Returns the response without that list, and the meetings it names; none when the
+// model left the list out.
+pub fn split_response(response: &str, emails: &[Email]) -> (String, Option<Vec<Meeting>>) {
+    let mut offset = 0;
+    let mut marker = None;
+    for line in response.split_inclusive('\n') {
+        if is_marker(line) {
+            marker = Some((offset, offset + line.len()));
+        }
+        offset += line.len();
+    }
+    let Some((start, end)) = marker else {
+        return (response.to_string(), None);
+    };
+
+    let meetings = response[end..]
+        .lines()
+        .map(|line| line.trim().trim_start_matches("- "))
+        .filter(|line| line.starts_with('{'))
+        .filter_map(|line| serde_json::from_str::<Listed>(line).ok())
+        .filter(|listed| !listed.title.trim().is_empty())
+        .map(|listed| {
+            let reference = listed.email.trim().trim_start_matches('#').to_lowercase();
+            Meeting {
+                title: listed.title.trim().to_string(),
+                when: listed.when.trim().to_string(),
+                organizer: listed.organizer.trim().to_string(),
+                email_id: emails
+                    .iter()
+                    .find(|email| !reference.is_empty() && cite::reference(&email.id) == reference)
+                    .map(|email| email.id.clone())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    (response[..start].to_string(), Some(meetings))
+}
+
+pub fn build(
+    meeting: &Meeting,
+    index: &Index,
+    contacts: &Book,
+    actions: &Tracker,
+    user: Option<&str>,
+    today: NaiveDate,
+) -> Dossier {
+    let invite = archive::load(&meeting.email_id);
+
+    let mut attendees: Vec<Attendee> = Vec::new();
+    for entry in invite.iter().flat_map(participants) {
+        let address = fallback::address(&entry);
+        if !address.contains('@')
+            || user == Some(address.as_str())
+            || attendees.iter().any(|known| known.address == address)
+        {
+            continue;
+        }
+
+        let contact = contacts.get(&address).filter(|contact| !contact.suggested);
+        attendees.push(Attendee {
+            name: contact
+                .map(|contact| contact.name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| fallback::display_name(&entry)),
+            about: contact
+                .map(|contact| contact.describe())
+                .unwrap_or_default(),
+            address,
+        });
+    }
+
+    let topic = mute::thread_key(&meeting.title);
+    let topic_words = keywords(&topic);
+
+    // Mail on the subject, then mail each attendee is actually on
+    let mut threads: Vec<Email> = invite.into_iter().collect();
+    let candidates = emails(index.search(&topic))
+        .filter(|email| same_subject(&topic_words, email))
+        .chain(attendees.iter().flat_map(|attendee| {
+            emails(index.search(&attendee.address)).filter(|email| {
+                participants(email)
+                    .iter()
+                    .any(|entry| fallback::address(entry) == attendee.address)
+            })
+        }));
+    for email in candidates {
+        if !threads.iter().any(|known| known.id == email.id) {
+            threads.push(email);
+        }
+    }
+    threads.sort_by_key(|email| Reverse(mail::received_at(email)));
+    threads.truncate(MAX_THREADS);
+
+    // Open items from these threads or asked by an attendee, matched on first names
+    let first_name = |name: &str| {
+        name.split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+    let questions = actions
+        .pending(today)
+        .into_iter()
+        .filter(|item| {
+            item.email_id
+                .as_ref()
+                .is_some_and(|id| threads.iter().any(|email| email.id == *id))
+                || (!item.person.trim().is_empty()
+                    && attendees
+                        .iter()
+                        .any(|attendee| first_name(&attendee.name) == first_name(&item.person)))
+        })
+        .cloned()
+        .collect();
+
+    let mut mentions: Vec<Hit> = Vec::new();
+    let queries = std::iter::once(topic.clone()).chain(
+        attendees
+            .iter()
+            .filter(|attendee| attendee.name != attendee.address)
+            .map(|attendee| attendee.name.clone()),
+    );
+    for hit in queries.flat_map(|query| index.search(&query)) {
+        if matches!(hit.target, Target::Briefing(_))
+            && !mentions.iter().any(|known| known.target == hit.target)
+        {
+            mentions.push(hit);
+        }
+    }
+    mentions.truncate(MAX_MENTIONS);
+
+    Dossier {
+        meeting: meeting.clone(),
+        attendees,
+        threads,
+        questions,
+        mentions,
+        note: None,
+    }
+}
+
+// The note first, then what it was written from; threads and briefings open on click
+pub fn view(dossier: &Dossier) -> Element<'_, Message> {
+    let link = |title: String, detail: String, target: Target| {
+        button(
+            column![
+                text(title).font(fonts::body()).size(fonts::scaled(13)),
+                text(detail)
+                    .font(fonts::body())
+                    .size(fonts::scaled(11))
+                    .style(secondary_text),
+            ]
+            .spacing(2),
+        )
+        .on_press(Message::OpenHit(target))
+        .width(Length::Fill)
+        .style(|theme: &Theme, _state| button::Style {
+            background: Some(theme.palette().text.scale_alpha(0.03).into()),
+            border: Border {
+                radius: iced::border::Radius::from(6.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    };
+    let meeting = &dossier.meeting;
+
+    let note: Element<Message> = match &dossier.note {
+        None => text("Writing the prep note...")
+            .font(fonts::body())
+            .size(fonts::scaled(14))
+            .style(secondary_text)
+            .into(),
+        Some(Ok(note)) => text(note)
+            .font(fonts::body())
+            .size(fonts::scaled(16))
+            .into(),
+        Some(Err(e)) => text(format!("No prep note: {}", e))
+            .font(fonts::body())
+            .size(fonts::scaled(13))
+            .style(warning_text)
+            .into(),
+    };
+
+    let mut page = column![
+        button(text("‹ Back").font(fonts::body()).size(fonts::scaled(12)))
+            .on_press(Message::ClosePrep)
+            .style(|theme: &Theme, _state| button::Style {
+                background: Some(theme.palette().background.into()),
+                text_color: theme.palette().text.scale_alpha(0.7),
+                ..Default::default()
+            }),
+        text(&meeting.title)
+            .font(fonts::body())
+            .size(fonts::scaled(18)),
+        text(format!(
+            "{} · organized by {}",
+            meeting.when, meeting.organizer
+        ))
+        .font(fonts::body())
+        .size(fonts::scaled(12))
+        .style(secondary_text),
+        note,
+    ]
+    .spacing(10);
+
+    if !dossier.attendees.is_empty() {
+        page = page.push(section_heading("Attendees"));
+    }
+    for attendee in &dossier.attendees {
+        page = page.push(
+            column![
+                text(format!("{} <{}>", attendee.name, attendee.address))
+                    .font(fonts::body())
+                    .size(fonts::scaled(13)),
+                (!attendee.about.is_empty()).then(|| text(&attendee.about)
+                    .font(fonts::body())
+                    .size(fonts::scaled(11))
+                    .style(secondary_text)),
+            ]
+            .spacing(2),
+        );
+    }
+
+    if !dossier.questions.is_empty() {
+        page = page.push(section_heading("Open questions"));
+    }
+    for item in &dossier.questions {
+        let detail = item.describe();
+        page = page.push(
+            text(if detail.is_empty() {
+                item.task.clone()
+            } else {
+                format!("{} ({})", item.task, detail)
+            })
+            .font(fonts::body())
+            .size(fonts::scaled(13)),
+        );
+    }
+
+    if !dossier.threads.is_empty() {
+        page = page.push(section_heading("Related emails"));
+    }
+    for email in &dossier.threads {
+        page = page.push(link(
+            email.subject.clone(),
+            format!("{} · {}", fallback::display_name(&email.from), email.date),
+            Target::Email(email.id.clone()),
+        ));
+    }
+
+    if !dossier.mentions.is_empty() {
+        page = page.push(section_heading("Earlier briefings"));
+    }
+    for hit in &dossier.mentions {
+        page = page.push(link(
+            hit.title.clone(),
+            hit.snippet.clone(),
+            hit.target.clone(),
+        ));
+    }
+
+    page.into()
+}
+
+// Each meeting the shown briefing mentions, with its Prep button
+pub fn meetings_view(meetings: &[Meeting]) -> Element<'_, Message> {
+    column![section_heading("Meetings")]
+        .extend(meetings.iter().enumerate().map(|(index, meeting)| {
+            row![
+                column![
+                    text(&meeting.title)
+                        .font(fonts::body())
+                        .size(fonts::scaled(14)),
+                    text(format!("{} · {}", meeting.when, meeting.organizer))
+                        .font(fonts::body())
+                        .size(fonts::scaled(11))
+                        .style(secondary_text),
+                ]
+                .spacing(2)
+                .width(Length::Fill),
+                small_button("Prep", Message::PrepMeeting(index)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+            .into()
+        }))
+        .spacing(8)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(id: &str) -> Email {
+        Email {
+            id: String::from(id),
+            subject: String::new(),
+            body: String::new(),
+            from: String::new(),
+            to: String::new(),
+            cc: String::new(),
+            date: String::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn split_response_reads_meetings_after_the_marker() {
+        let emails = [email("<sync@mail>")];
+        let response = format!(
+            "Good morning.\n\nPROJECTS\nNONE\n\n**Meetings**\n\
+             {{\"title\": \"ScyAI sync\", \"when\": \"Thursday 7pm\", \"organizer\": \"Bernhard\", \"email\": \"#{}\"}}\n\
+             - {{\"title\": \"Lunch with Ada\"}}\n{{\"when\": \"Friday\"}}\nNONE\n",
+            cite::reference("<sync@mail>")
+        );
+
+        let (rest, meetings) = split_response(&response, &emails);
+
+        assert_eq!(rest, "Good morning.\n\nPROJECTS\nNONE\n\n");
+        let meetings = meetings.unwrap();
+        assert_eq!(meetings.len(), 2);
+        assert_eq!(meetings[0].title, "ScyAI sync");
+        assert_eq!(meetings[0].when, "Thursday 7pm");
+        assert_eq!(meetings[0].email_id, "<sync@mail>");
+        assert_eq!(meetings[1].title, "Lunch with Ada");
+        assert_eq!(meetings[1].email_id, "");
+    }
+
+    #[test]
+    fn split_response_without_marker_leaves_meetings_to_the_fallback() {
+        let (rest, meetings) = split_response("Good morning.", &[]);
+
+        assert_eq!(rest, "Good morning.");
+        assert!(meetings.is_none());
+
+        let (_, meetings) = split_response("Good morning.\n\nMEETINGS\nNONE", &[]);
+        assert_eq!(meetings, Some(Vec::new()));
+    }
+}
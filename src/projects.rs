@@ -0,0 +1,330 @@
+use crate::history::History;
+use crate::search::Target;
+use crate::{Message, fonts, hint_text, secondary_text, small_button};
+use chrono::{DateTime, Duration, Local};
+use iced::widget::{column, row, text};
+use iced::{Element, Length};
+use serde::{Deserialize, Serialize};
+
+// Ongoing projects and topics followed from one briefing to the next. The model reports what
+// moved on each after its action items; the running summaries go back into the next prompt so
+// it can say how things changed since they last came up.
+
+// Projects untouched this long are left out of the prompt, though still listed
+const STALE_DAYS: i64 = 30;
+
+// Most projects, and timeline entries per project, the prompt recalls
+const PROMPT_PROJECTS: usize = 10;
+const PROMPT_ENTRIES: usize = 3;
+
+// A project as reported in one briefing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Update {
+    pub project: String,
+    // What happened in this briefing's mail
+    pub update: String,
+    // Where the project stands now, replacing the previous summary
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Local>,
+    pub briefing_id: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub name: String,
+    pub summary: String,
+    pub updated_at: DateTime<Local>,
+    // Oldest first
+    pub timeline: Vec<Entry>,
+}
+
+impl Project {
+    pub fn is_stale(&self, now: DateTime<Local>) -> bool {
+        now - self.updated_at > Duration::days(STALE_DAYS)
+    }
+
+    pub fn recent(&self, count: usize) -> &[Entry] {
+        &self.timeline[self.timeline.len().saturating_sub(count)..]
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Projects {
+    projects: Vec<Project>,
+}
+
+fn same(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+impl Projects {
+    // Most recently updated first
+    pub fn all(&self) -> Vec<&Project> {
+        let mut projects = self.projects.iter().collect::<Vec<&Project>>();
+        projects.sort_by_key(|project| std::cmp::Reverse(project.updated_at));
+
+        projects
+    }
+
+    // What the next prompt is reminded of
+    pub fn active(&self, now: DateTime<Local>) -> Vec<Project> {
+        self.all()
+            .into_iter()
+            .filter(|project| !project.is_stale(now))
+            .take(PROMPT_PROJECTS)
+            .map(|project| Project {
+                timeline: project.recent(PROMPT_ENTRIES).to_vec(),
+                ..project.clone()
+            })
+            .collect()
+    }
+
+    // A briefing's updates extend the timelines of projects it names again, matched by name. An
+    // update already recorded for the briefing, or repeating the last entry, is left out.
+    pub fn merge(&mut self, briefing_id: u64, updates: &[Update], now: DateTime<Local>) {
+        for update in updates {
+            let name = update.project.trim();
+            if name.is_empty() {
+                continue;
+            }
+
+            let project = match self.projects.iter().position(|p| same(&p.name, name)) {
+                Some(index) => &mut self.projects[index],
+                None => {
+                    self.projects.push(Project {
+                        name: name.to_string(),
+                        summary: String::new(),
+                        updated_at: now,
+                        timeline: Vec::new(),
+                    });
+                    self.projects.last_mut().unwrap()
+                }
+            };
+
+            let text = update.update.trim();
+            let repeated = project
+                .timeline
+                .iter()
+                .any(|e| e.briefing_id == briefing_id)
+                || project.timeline.last().is_some_and(|e| e.text == text);
+            if repeated {
+                continue;
+            }
+
+            if !update.summary.trim().is_empty() {
+                project.summary = update.summary.trim().to_string();
+            }
+            if !text.is_empty() {
+                project.timeline.push(Entry {
+                    at: now,
+                    briefing_id,
+                    text: text.to_string(),
+                });
+            }
+            project.updated_at = now;
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.projects.retain(|project| !same(&project.name, name));
+    }
+}
+
+#[derive(Deserialize)]
+struct Listed {
+    project: String,
+    #[serde(default)]
+    update: String,
+    #[serde(default)]
+    summary: String,
+}
+
+fn is_marker(line: &str) -> bool {
+    line.trim()
+        .trim_matches(|c: char| c == '*' || c == '#' || c == ':' || c.is_whitespace())
+        .eq_ignore_ascii_case("projects")
+}
+
+// The model lists projects after a line reading PROJECTS, one JSON object per line, following
+// its action items. Returns the response without that list, and the updates it names.
+pub fn split_response(response: &str) -> (String, Vec<Update>) {
+    let mut offset = 0;
+    let mut marker = None;
+    for line in response.split_inclusive('\n') {
+        if is_marker(line) {
+            marker = Some((offset, offset + line.len()));
+        }
+        offset += line.len();
+    }
+    let Some((start, end)) = marker else {
+        return (response.to_string(), Vec::new());
+    };
+
+    let updates = response[end..]
+        .lines()
+        .map(|line| line.trim().trim_start_matches("- "))
+        .filter(|line| line.starts_with('{'))
+        .filter_map(|line| serde_json::from_str::<Listed>(line).ok())
+        .filter(|listed| !listed.project.trim().is_empty())
+        .map(|listed| Update {
+            project: listed.project,
+            update: listed.update,
+            summary: listed.summary,
+        })
+        .collect();
+
+    (response[..start].to_string(), updates)
+}
+
+// Each project's standing and its timeline, newest first, with the briefings behind it
+pub fn view<'a>(projects: &'a Projects, history: &History) -> Element<'a, Message> {
+    let projects = projects.all();
+    if projects.is_empty() {
+        return text("Projects appear here as briefings follow them from day to day")
+            .font(fonts::body())
+            .size(fonts::scaled(14))
+            .into();
+    }
+
+    let now = Local::now();
+    let mut list = column![].spacing(18);
+    for project in projects {
+        let mut card = column![
+            row![
+                text(&project.name)
+                    .font(fonts::body())
+                    .size(fonts::scaled(15))
+                    .width(Length::Fill),
+                text(if project.is_stale(now) {
+                    format!("quiet since {}", project.updated_at.format("%b %-d"))
+                } else {
+                    format!("updated {}", project.updated_at.format("%b %-d"))
+                })
+                .font(fonts::body())
+                .size(fonts::scaled(11))
+                .style(hint_text),
+                small_button("Forget", Message::ForgetProject(project.name.clone())),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            (!project.summary.is_empty()).then(|| text(&project.summary)
+                .font(fonts::body())
+                .size(fonts::scaled(13))
+                .style(secondary_text)),
+        ]
+        .spacing(6);
+
+        for entry in project.timeline.iter().rev() {
+            card = card.push(
+                row![
+                    text(entry.at.format("%b %-d").to_string())
+                        .font(fonts::body())
+                        .size(fonts::scaled(11))
+                        .style(hint_text)
+                        .width(Length::Fixed(56.0)),
+                    text(&entry.text)
+                        .font(fonts::body())
+                        .size(fonts::scaled(13))
+                        .width(Length::Fill),
+                    history.get(entry.briefing_id).map(|_| small_button(
+                        "Briefing",
+                        Message::OpenHit(Target::Briefing(entry.briefing_id))
+                    )),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+
+        list = list.push(card);
+    }
+
+    list.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(project: &str, text: &str, summary: &str) -> Update {
+        Update {
+            project: String::from(project),
+            update: String::from(text),
+            summary: String::from(summary),
+        }
+    }
+
+    #[test]
+    fn merge_follows_a_project_by_name() {
+        let mut projects = Projects::default();
+        let now = Local::now();
+
+        projects.merge(1, &[update("Launch", "Date set.", "Planned for May.")], now);
+        projects.merge(2, &[update(" launch ", "Slipped a week.", "")], now);
+
+        let all = projects.all();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].name, "Launch");
+        assert_eq!(all[0].summary, "Planned for May.");
+        assert_eq!(
+            all[0]
+                .timeline
+                .iter()
+                .map(|e| (e.briefing_id, e.text.as_str()))
+                .collect::<Vec<_>>(),
+            [(1, "Date set."), (2, "Slipped a week.")]
+        );
+    }
+
+    #[test]
+    fn merge_skips_repeated_updates() {
+        let mut projects = Projects::default();
+        let then = Local::now() - Duration::days(2);
+        let now = Local::now();
+
+        projects.merge(1, &[update("Launch", "Date set.", "Planned.")], then);
+        // The same briefing merged again, then a later one saying nothing new
+        projects.merge(1, &[update("Launch", "Other text.", "Changed.")], now);
+        projects.merge(2, &[update("Launch", "Date set.", "Changed.")], now);
+
+        let project = projects.all()[0];
+        assert_eq!(project.timeline.len(), 1);
+        assert_eq!(project.summary, "Planned.");
+        assert_eq!(project.updated_at, then);
+    }
+
+    #[test]
+    fn split_response_takes_the_list_after_the_marker() {
+        let response = "Good morning.\n\nACTION ITEMS\n{\"task\": \"Reply\"}\n\n**PROJECTS:**\n\
+            {\"project\": \"Launch\", \"update\": \"Date set.\", \"summary\": \"May.\"}\n\
+            - {\"project\": \"Hiring\"}\nnot json\n{\"project\": \" \"}\n";
+
+        let (rest, updates) = split_response(response);
+
+        assert_eq!(
+            rest,
+            "Good morning.\n\nACTION ITEMS\n{\"task\": \"Reply\"}\n\n"
+        );
+        assert_eq!(
+            updates,
+            [
+                update("Launch", "Date set.", "May."),
+                update("Hiring", "", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_response_without_marker_keeps_everything() {
+        let (rest, updates) = split_response("Good morning.\n\nNothing else.");
+
+        assert_eq!(rest, "Good morning.\n\nNothing else.");
+        assert!(updates.is_empty());
+    }
+}
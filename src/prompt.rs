@@ -0,0 +1,381 @@
+use crate::actions::{ActionItem, Found};
+use crate::contacts::{Book, Contact};
+use crate::history::{Briefing, Kind};
+use crate::mail;
+use crate::prep::Dossier;
+use crate::projects::Project;
+use chrono::{DateTime, Local};
+
+pub const BRIEFING_TEMPLATE: &str = r#"<system_capability>
+    You are an elite Executive Assistant and Chief of Staff. Your goal is to synthesize high-volume information into calm, actionable intelligence. You value clarity, brevity, and narrative flow over lists and formatting.
+    </system_capability>
+
+    <strict_authority_protocol>
+    ### FORMATTING CONSTANTS - READ CAREFULLY
+    1.  **PLAIN TEXT ONLY**: You are STRICTLY FORBIDDEN from using Markdown.
+        -   NO bolding (**text**).
+        -   NO italics (*text*).
+        -   NO headers (###).
+        -   NO bullet points (-) or numbered lists (1.).
+    2.  **PARAGRAPHS**: Content must be delivered in smooth, readable paragraphs.
+    3.  **FAILURE CONDITION**: If the output contains a single asterisk or bullet point, the response is considered a failure.
+    4.  **CITATIONS**: Every email has an ID. End each sentence that draws on an email with its ID in square brackets, like [#1a2b3c4d], or [#1a2b3c4d, #5e6f7a8b] for several. Never invent an ID.
+    </strict_authority_protocol>
+
+    <processing_logic>
+    Step 1: **FILTER**. Aggressively discard trivial emails (newsletters, receipts, notifications, "checking in" emails) unless they contain a direct blocker or urgent deadline - USERS DO NOT WANT SPAM IN THEIR BREIFING.
+    Step 2: **EXTRACT**. Identify:
+        -   Upcoming meetings (Who, When, Context).
+        -   Direct questions asked of the user.
+        -   Urgent blockers or red flags.
+        -   Status updates on active projects.
+    Step 3: **SYNTHESIZE**. Draft a briefing using a calm, professional tone.
+        -   Start with "Good day, Apex.".
+        -   Group related items into paragraphs (e.g., Meeting context in para 1, Project blockers in para 2).
+        -   End with a strategic next step if applicable.
+    </processing_logic>
+
+    <few_shot_examples>
+    Input: [Raw Emails containing: 1. Newsletter from Substack (ID 0c4f9e21), 2. Meeting reminder for ScyAI at 7pm (ID 7d2a61b0), 3. Email from Bernhard about missing login screen (ID a93e5f14), 4. WhatsApp group chatter about QR codes vs Roam (ID 52be0c8d), 5. SuperWhisper team update on landing page (ID e817d3a6).]
+
+    Output:
+    Good day, Apex.
+
+    You have a meeting coming up in about 3.5 hours - ScyAI x UI/UX Sync at 7pm with Bernhard [#7d2a61b0]. Before that call, you should know that Bernhard flagged a missing login screen in the ScyAI Design group. They're implementing one-time passwords for first login, but users need to change their password immediately after. He's looking for that additional screen to be designed [#a93e5f14].
+
+    Also in your WhatsApp groups, someone from the Visualizations/Branding Co is asking about QR codes and whether you prefer communication through that chat or Roam. Julian pushed back hard on QR codes, but the original question about your preferred communication method is still hanging [#52be0c8d].
+
+    Your SuperWhisper team has been busy - they've got a new landing page ready for feedback. The conversation shows they've been iterating on animations and user experience, with some good discussion about making the demo less interactive during autoplay [#e817d3a6].
+
+    I'd prioritize prepping for the ScyAI meeting by reviewing that missing login screen requirement. The day looks manageable with just the one evening meeting.
+    </few_shot_examples>
+
+    <task>
+    Summarize the following raw emails, which came in overnight, into a morning briefing that leads with today's agenda, following the strict formatting protocols above.
+
+    EMAILS:
+    {emails}
+    </task>"#;
+
+pub const END_OF_DAY_TEMPLATE: &str = r#"<system_capability>
+    You are an elite Executive Assistant and Chief of Staff wrapping up the working day. You value clarity, brevity, and narrative flow over lists and formatting.
+    </system_capability>
+
+    <strict_authority_protocol>
+    ### FORMATTING CONSTANTS - READ CAREFULLY
+    1.  **PLAIN TEXT ONLY**: You are STRICTLY FORBIDDEN from using Markdown. No bolding, italics, headers, bullet points or numbered lists.
+    2.  **PARAGRAPHS**: Content must be delivered in smooth, readable paragraphs.
+    3.  **CITATIONS**: Every email has an ID. End each sentence that draws on an email with its ID in square brackets, like [#1a2b3c4d], or [#1a2b3c4d, #5e6f7a8b] for several. Never invent an ID.
+    </strict_authority_protocol>
+
+    <processing_logic>
+    Step 1: **FILTER**. Discard newsletters, receipts and notifications unless they carry a blocker or a deadline.
+    Step 2: **RECAP**. What came in today that matters: decisions made, news on active projects, anything that changed plans.
+    Step 3: **STILL OPEN**. Questions and requests that are still waiting on the user, and anything due tomorrow morning.
+    Step 4: **SYNTHESIZE**. Start with "Good evening, Apex.", keep it shorter than a morning briefing, and end with what to pick up first tomorrow.
+    </processing_logic>
+
+    <task>
+    Summarize the following raw emails, which came in today, into an end-of-day wrap-up following the strict formatting protocols above.
+
+    EMAILS:
+    {emails}
+    </task>"#;
+
+pub const WEEKLY_TEMPLATE: &str = r#"<system_capability>
+    You are an elite Executive Assistant and Chief of Staff writing the weekly digest. You value clarity, brevity, and narrative flow over lists and formatting.
+    </system_capability>
+
+    <strict_authority_protocol>
+    ### FORMATTING CONSTANTS - READ CAREFULLY
+    1.  **PLAIN TEXT ONLY**: You are STRICTLY FORBIDDEN from using Markdown. No bolding, italics, headers, bullet points or numbered lists.
+    2.  **PARAGRAPHS**: Content must be delivered in smooth, readable paragraphs.
+    </strict_authority_protocol>
+
+    <processing_logic>
+    Step 1: **THEMES**. Read the week's briefings and find the threads that ran through several days: projects, people, recurring blockers.
+    Step 2: **OUTCOMES**. What got decided or finished this week, including the action items that were checked off.
+    Step 3: **CARRY OVER**. What is still open going into next week, oldest and most overdue first.
+    Step 4: **SYNTHESIZE**. Start with "Good day, Apex. Here is your week.", give one paragraph per theme, and end with the two or three things that matter most next week.
+    </processing_logic>
+
+    <task>
+    Write the weekly digest from the following briefings, oldest first, following the strict formatting protocols above.
+
+    BRIEFINGS:
+    {briefings}
+    </task>"#;
+
+pub fn template(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Morning => BRIEFING_TEMPLATE,
+        Kind::EndOfDay => END_OF_DAY_TEMPLATE,
+        Kind::Weekly => WEEKLY_TEMPLATE,
+    }
+}
+
+// Where the material goes: mail for the daily kinds, stored briefings for the weekly digest
+pub fn placeholder(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Weekly => "{briefings}",
+        _ => "{emails}",
+    }
+}
+
+// What the app already knows, given to the model alongside the mail
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    pub open_items: Vec<ActionItem>,
+    pub previous: Option<Previous>,
+    // Report only what changed since the previous briefing
+    pub delta: bool,
+    // For the weekly digest: the briefings in its window, oldest first, and the items checked
+    // off meanwhile
+    pub briefings: Vec<Briefing>,
+    pub done_items: Vec<ActionItem>,
+    // Everyone the user has described; only those on the fetched mail reach the prompt
+    pub contacts: Book,
+    // Running summaries of the projects recent briefings followed
+    pub projects: Vec<Project>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Previous {
+    pub id: u64,
+    pub created_at: DateTime<Local>,
+    pub items: Vec<Found>,
+}
+
+// Asked of every briefing, whatever the template, so the tracker can read the list back
+const ACTION_ITEMS_INSTRUCTIONS: &str = r##"<action_items>
+    After the briefing, write a line containing only ACTION ITEMS. Below it, write one JSON object per line for each direct question or request the user still has to act on, and nothing else:
+    {"task": "Design the password change screen", "person": "Bernhard", "email": "#a93e5f14", "due": "2025-01-31"}
+    Use the ID of the email the item comes from. Leave "due" empty unless a date is given or implied; today is {today}. Write NONE below the line when there are no items.
+    </action_items>"##;
+
+// Asked after the action items so the project memory can follow each topic from day to day
+const PROJECTS_INSTRUCTIONS: &str = r##"<projects_update>
+    After the action items, write a line containing only PROJECTS. Below it, write one JSON object per line for each ongoing project or topic these emails move forward, and nothing else:
+    {"project": "ScyAI", "update": "Bernhard sent the revised login screen", "summary": "Login redesign in review; the user owes feedback by Friday"}
+    "update" says what happened in these emails, "summary" where the project stands now. Reuse the names under <projects> when an email continues one of them. Write NONE below the line when no project moved.
+    </projects_update>"##;
+
+// Asked last so meeting prep can offer every meeting the model noticed, not only those a
+// pattern can spot
+const MEETINGS_INSTRUCTIONS: &str = r##"<meetings>
+    After the projects, write a line containing only MEETINGS. Below it, write one JSON object per line for each meeting, call or event these emails arrange or mention, and nothing else:
+    {"title": "ScyAI sync", "when": "Thursday 7pm", "organizer": "Bernhard", "email": "#7d2a61b0"}
+    Use the ID of the email that mentions it. Write NONE below the line when there are no meetings.
+    </meetings>"##;
+
+fn listed(task: &str, detail: String) -> String {
+    if detail.is_empty() {
+        format!("    - {}", task)
+    } else {
+        format!("    - {} ({})", task, detail)
+    }
+}
+
+fn items_section(tag: &str, intro: &str, items: &[ActionItem]) -> String {
+    let listed = items
+        .iter()
+        .map(|item| listed(&item.task, item.describe()))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!("<{}>\n    {}\n{}\n    </{}>", tag, intro, listed, tag)
+}
+
+fn delta_section(previous: &Previous) -> String {
+    let items = if previous.items.is_empty() {
+        String::from("    (no items)")
+    } else {
+        previous
+            .items
+            .iter()
+            .map(|item| listed(&item.task, item.describe()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    format!(
+        "<delta_mode>\n    The user has already read the previous briefing, written {}. It listed these items:\n{}\n    Write only what is new since then: new developments, items that are now resolved, and escalations such as a closer deadline, a repeated ask or a sharper tone. Leave out anything that has not changed, and if nothing has, say so in one sentence.\n    Give every object under ACTION ITEMS a \"status\" of \"new\", \"updated\", \"escalated\" or \"resolved\" compared with that list, and include resolved items so they can be checked off.\n    </delta_mode>",
+        previous.created_at.format("%A %b %-d at %-I:%M %p"),
+        items
+    )
+}
+
+fn projects_section(projects: &[Project]) -> String {
+    let listed =
+        projects
+            .iter()
+            .map(|project| {
+                let mut lines = vec![listed(
+                    &project.name,
+                    format!("last mentioned {}", project.updated_at.format("%A %b %-d")),
+                )];
+                if !project.summary.is_empty() {
+                    lines.push(format!("      Now: {}", project.summary));
+                }
+                lines.extend(project.timeline.iter().map(|entry| {
+                    format!("      {}: {}", entry.at.format("%a %b %-d"), entry.text)
+                }));
+                lines.join("\n")
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+    format!(
+        "<projects>\n    Earlier briefings followed these projects. When the new emails move one of them on or settle it, say so against what came before, such as \"the login screen issue from Tuesday is now resolved\". Do not recap projects the emails do not touch.\n{}\n    </projects>",
+        listed
+    )
+}
+
+fn people_section(people: &[&Contact]) -> String {
+    let listed = people
+        .iter()
+        .map(|contact| listed(&contact.title(), contact.describe()))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!(
+        "<people>\n    The user keeps these notes on people in the emails. Use them to say who someone is and why their message matters, and put VIPs first. Do not quote the notes back.\n{}\n    </people>",
+        listed
+    )
+}
+
+pub fn briefing_prompt(
+    template: &str,
+    formatted_emails: &str,
+    people: &[&Contact],
+    context: &Context,
+) -> String {
+    let mut prompt = template.replace("{emails}", formatted_emails);
+
+    if !people.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&people_section(people));
+    }
+
+    if !context.projects.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&projects_section(&context.projects));
+    }
+
+    if !context.open_items.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&items_section(
+            "open_action_items",
+            "These items from earlier briefings are still open. Where one is still relevant, remind the user near the end in a sentence starting \"Still waiting on you:\". Do not list them again under ACTION ITEMS unless the new emails mention them.",
+            &context.open_items,
+        ));
+    }
+
+    if context.delta
+        && let Some(previous) = &context.previous
+    {
+        prompt.push_str("\n\n");
+        prompt.push_str(&delta_section(previous));
+    }
+
+    prompt.push_str("\n\n");
+    prompt.push_str(
+        &ACTION_ITEMS_INSTRUCTIONS.replace("{today}", &Local::now().format("%Y-%m-%d").to_string()),
+    );
+    prompt.push_str("\n\n");
+    prompt.push_str(PROJECTS_INSTRUCTIONS);
+    prompt.push_str("\n\n");
+    prompt.push_str(MEETINGS_INSTRUCTIONS);
+
+    prompt
+}
+
+// The week's briefings stand in for its mail, and the tracker says what got done
+pub fn weekly_prompt(template: &str, formatted_briefings: &str, context: &Context) -> String {
+    let mut prompt = template.replace("{briefings}", formatted_briefings);
+
+    if !context.done_items.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&items_section(
+            "done_this_week",
+            "The user checked off these action items this week.",
+            &context.done_items,
+        ));
+    }
+    if !context.open_items.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&items_section(
+            "still_open",
+            "These action items are still open going into next week.",
+            &context.open_items,
+        ));
+    }
+
+    prompt
+}
+
+const PREP_TEMPLATE: &str = r#"<task>
+    Write a short note the user can read just before the meeting below. Say what the meeting is for, what each attendee is likely to want, which open questions to settle, and anything to prepare or decide beforehand. Write plain text without Markdown, in under 200 words.
+    </task>
+
+    <meeting>
+    {meeting}
+    </meeting>"#;
+
+// Everything in the dossier, for a note on top of it
+pub fn prep_prompt(dossier: &Dossier) -> String {
+    let meeting = &dossier.meeting;
+    let mut prompt = PREP_TEMPLATE.replace(
+        "{meeting}",
+        &format!(
+            "{}, {}, organized by {}",
+            meeting.title, meeting.when, meeting.organizer
+        ),
+    );
+
+    if !dossier.attendees.is_empty() {
+        let listed = dossier
+            .attendees
+            .iter()
+            .map(|attendee| {
+                listed(
+                    &format!("{} <{}>", attendee.name, attendee.address),
+                    attendee.about.clone(),
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        prompt.push_str(&format!("\n\n<attendees>\n{}\n    </attendees>", listed));
+    }
+
+    if !dossier.questions.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&items_section(
+            "open_questions",
+            "These action items involving the attendees are still open.",
+            &dossier.questions,
+        ));
+    }
+
+    if !dossier.mentions.is_empty() {
+        let listed = dossier
+            .mentions
+            .iter()
+            .map(|hit| format!("    - {}: {}", hit.title, hit.snippet))
+            .collect::<Vec<String>>()
+            .join("\n");
+        prompt.push_str(&format!(
+            "\n\n<prior_briefings>\n    Earlier briefings said this about the subject or the people.\n{}\n    </prior_briefings>",
+            listed
+        ));
+    }
+
+    if !dossier.threads.is_empty() {
+        prompt.push_str(&format!(
+            "\n\n<related_emails>\n{}\n    </related_emails>",
+            mail::email_formatter(&dossier.threads)
+        ));
+    }
+
+    prompt
+}
@@ -0,0 +1,181 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, Weekday};
+
+// A schedule is a list of rules like "mon-fri 07:30; sat 09:00 17:00"
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub days: Vec<Weekday>,
+    pub times: Vec<NaiveTime>,
+}
+
+const WEEK: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+fn parse_day(day: &str) -> Result<Weekday, String> {
+    day.parse::<Weekday>()
+        .map_err(|_| format!("Unknown weekday \"{}\"", day))
+}
+
+fn parse_days(days: &str) -> Result<Vec<Weekday>, String> {
+    match days {
+        "daily" | "*" => return Ok(WEEK.to_vec()),
+        "weekdays" => return Ok(WEEK[..5].to_vec()),
+        "weekends" => return Ok(WEEK[5..].to_vec()),
+        _ => {}
+    }
+
+    let mut parsed = Vec::new();
+    for part in days.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first = parse_day(first)?.num_days_from_monday() as usize;
+                let last = parse_day(last)?.num_days_from_monday() as usize;
+                if first > last {
+                    return Err(format!("Day range \"{}\" runs backwards", part));
+                }
+                parsed.extend_from_slice(&WEEK[first..=last]);
+            }
+            None => parsed.push(parse_day(part)?),
+        }
+    }
+
+    Ok(parsed)
+}
+
+pub fn parse(spec: &str) -> Result<Vec<Rule>, String> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            let mut parts = rule.split_whitespace();
+            let days = parse_days(&parts.next().unwrap_or_default().to_lowercase())?;
+            let times = parts
+                .map(|time| {
+                    NaiveTime::parse_from_str(time, "%H:%M")
+                        .map_err(|_| format!("Invalid time \"{}\", expected HH:MM", time))
+                })
+                .collect::<Result<Vec<NaiveTime>, String>>()?;
+
+            if times.is_empty() {
+                return Err(format!("Rule \"{}\" has no time", rule));
+            }
+
+            Ok(Rule { days, times })
+        })
+        .collect()
+}
+
+fn slots_on(rules: &[Rule], day: DateTime<Local>) -> Vec<DateTime<Local>> {
+    let date = day.date_naive();
+
+    rules
+        .iter()
+        .filter(|rule| rule.days.contains(&date.weekday()))
+        .flat_map(|rule| rule.times.iter())
+        .filter_map(|time| date.and_time(*time).and_local_timezone(Local).earliest())
+        .collect()
+}
+
+// The most recent scheduled time at or before now, looking back at most a week
+pub fn last_due(rules: &[Rule], now: DateTime<Local>) -> Option<DateTime<Local>> {
+    (0..=7)
+        .map(|days_back| now - Duration::days(days_back))
+        .flat_map(|day| slots_on(rules, day))
+        .filter(|slot| *slot <= now)
+        .max()
+}
+
+pub fn next_due(rules: &[Rule], now: DateTime<Local>) -> Option<DateTime<Local>> {
+    (0..=7)
+        .map(|days_ahead| now + Duration::days(days_ahead))
+        .flat_map(|day| slots_on(rules, day))
+        .filter(|slot| *slot > now)
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        // June 2025, well away from daylight saving changes; the 16th is a Monday
+        Local
+            .with_ymd_and_hms(2025, 6, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn parse_reads_ranges_lists_and_names() {
+        let rules = parse("mon-fri 07:30; Sat,sun 09:00 17:00;").unwrap();
+
+        assert_eq!(
+            rules,
+            [
+                Rule {
+                    days: WEEK[..5].to_vec(),
+                    times: vec![time(7, 30)],
+                },
+                Rule {
+                    days: vec![Weekday::Sat, Weekday::Sun],
+                    times: vec![time(9, 0), time(17, 0)],
+                },
+            ]
+        );
+        assert_eq!(parse("weekdays 08:00").unwrap()[0].days, WEEK[..5].to_vec());
+        assert_eq!(parse("weekends 08:00").unwrap()[0].days, WEEK[5..].to_vec());
+        assert_eq!(parse("daily 08:00").unwrap()[0].days, WEEK.to_vec());
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_rejects_bad_rules() {
+        let error = |spec: &str| parse(spec).unwrap_err();
+
+        assert!(error("fri-mon 08:00").contains("runs backwards"));
+        assert!(error("mon").contains("has no time"));
+        assert!(error("mon 7am").contains("Invalid time"));
+        assert!(error("mon 25:00").contains("Invalid time"));
+        assert!(error("funday 08:00").contains("Unknown weekday"));
+    }
+
+    #[test]
+    fn last_due_catches_up_across_the_weekend() {
+        let rules = parse("mon-fri 07:30").unwrap();
+
+        // Monday before the first run: Friday's is the one missed
+        assert_eq!(last_due(&rules, at(16, 7, 0)), Some(at(13, 7, 30)));
+        // On the slot itself it is due
+        assert_eq!(last_due(&rules, at(16, 7, 30)), Some(at(16, 7, 30)));
+        assert_eq!(last_due(&rules, at(18, 12, 0)), Some(at(18, 7, 30)));
+    }
+
+    #[test]
+    fn last_due_looks_back_a_whole_week() {
+        let rules = parse("mon 07:30").unwrap();
+
+        assert_eq!(last_due(&rules, at(16, 7, 0)), Some(at(9, 7, 30)));
+        assert_eq!(last_due(&[], at(16, 7, 0)), None);
+    }
+
+    #[test]
+    fn next_due_is_strictly_after_now() {
+        let rules = parse("mon-fri 07:30; sun 18:00").unwrap();
+
+        assert_eq!(next_due(&rules, at(15, 20, 0)), Some(at(16, 7, 30)));
+        assert_eq!(next_due(&rules, at(16, 7, 30)), Some(at(17, 7, 30)));
+        assert_eq!(next_due(&rules, at(20, 8, 0)), Some(at(22, 18, 0)));
+        assert_eq!(next_due(&[], at(16, 7, 0)), None);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::cite;
+use crate::history::Briefing;
+use crate::mail::Email;
+use std::collections::{HashMap, HashSet};
+
+const SNIPPET_RADIUS: usize = 80;
+const MAX_HITS: usize = 50;
+
+const STOP_WORDS: [&str; 40] = [
+    "a", "an", "and", "are", "as", "at", "be", "by", "did", "do", "does", "first", "for", "from",
+    "has", "have", "he", "her", "his", "how", "in", "is", "it", "me", "my", "of", "on", "or",
+    "she", "that", "the", "their", "they", "this", "to", "was", "what", "when", "who", "with",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Briefing(u64),
+    Email(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub target: Target,
+    pub title: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone)]
+struct Document {
+    target: Target,
+    title: String,
+    text: String,
+    // Unix timestamp, used to order hits of equal relevance oldest first
+    timestamp: i64,
+}
+
+// In-memory inverted index, rebuilt on startup from history and the email archive
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    documents: Vec<Document>,
+    postings: HashMap<String, Vec<(usize, u32)>>,
+}
+
+fn stem(word: &str) -> String {
+    for suffix in ["ing", "ed", "es", "s"] {
+        if word.len() > suffix.len() + 3
+            && let Some(stem) = word.strip_suffix(suffix)
+        {
+            return stem.to_string();
+        }
+    }
+
+    word.to_string()
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(|word| word.to_lowercase())
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .map(|word| stem(&word))
+        .collect()
+}
+
+fn snippet(text: &str, terms: &[String]) -> String {
+    let flat = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let lower = flat.to_lowercase();
+
+    let start = terms
+        .iter()
+        .filter_map(|term| lower.find(term.as_str()))
+        .min()
+        .unwrap_or(0);
+
+    // Lowercasing can shift byte offsets for some scripts, so clamp to a char boundary
+    let mut from = start.saturating_sub(SNIPPET_RADIUS).min(flat.len());
+    while !flat.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (start + SNIPPET_RADIUS).min(flat.len());
+    while !flat.is_char_boundary(to) {
+        to += 1;
+    }
+
+    let mut snippet = flat[from..to].trim().to_string();
+    if from > 0 {
+        snippet.insert_str(0, "...");
+    }
+    if to < flat.len() {
+        snippet.push_str("...");
+    }
+
+    snippet
+}
+
+impl Index {
+    pub fn build(briefings: &[Briefing], emails: &[Email]) -> Self {
+        let mut index = Self::default();
+
+        for briefing in briefings {
+            index.add_briefing(briefing);
+        }
+        for email in emails {
+            index.add_email(email);
+        }
+
+        index
+    }
+
+    fn add(&mut self, document: Document) {
+        if self.documents.iter().any(|d| d.target == document.target) {
+            return;
+        }
+
+        let position = self.documents.len();
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for token in tokenize(&format!("{} {}", document.title, document.text)) {
+            *counts.entry(token).or_default() += 1;
+        }
+        for (token, count) in counts {
+            self.postings
+                .entry(token)
+                .or_default()
+                .push((position, count));
+        }
+
+        self.documents.push(document);
+    }
+
+    pub fn add_briefing(&mut self, briefing: &Briefing) {
+        self.add(Document {
+            target: Target::Briefing(briefing.id),
+            title: format!("Briefing · {}", briefing.label()),
+            text: cite::numbered(briefing, &briefing.text),
+            timestamp: briefing.created_at.timestamp(),
+        });
+    }
+
+    pub fn add_email(&mut self, email: &Email) {
+        self.add(Document {
+            target: Target::Email(email.id.clone()),
+            title: format!("{} · {}", email.from, email.subject),
+            text: email.body.clone(),
+            timestamp: mailparse::dateparse(&email.date).unwrap_or_default(),
+        });
+    }
+
+    // Ranked by how many query terms match, then tf-idf, then oldest first
+    pub fn search(&self, query: &str) -> Vec<Hit> {
+        let terms = tokenize(query)
+            .into_iter()
+            .collect::<HashSet<String>>()
+            .into_iter()
+            .collect::<Vec<String>>();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let total = self.documents.len() as f64;
+        let mut scores: HashMap<usize, (usize, f64)> = HashMap::new();
+
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let idf = (total / postings.len() as f64).ln() + 1.0;
+
+            for (document, count) in postings {
+                let score = scores.entry(*document).or_default();
+                score.0 += 1;
+                score.1 += (1.0 + (*count as f64).ln()) * idf;
+            }
+        }
+
+        let mut ranked = scores.into_iter().collect::<Vec<(usize, (usize, f64))>>();
+        ranked.sort_by(|(a, (a_matched, a_score)), (b, (b_matched, b_score))| {
+            b_matched
+                .cmp(a_matched)
+                .then(b_score.total_cmp(a_score))
+                .then(
+                    self.documents[*a]
+                        .timestamp
+                        .cmp(&self.documents[*b].timestamp),
+                )
+        });
+
+        ranked
+            .into_iter()
+            .take(MAX_HITS)
+            .map(|(position, _)| {
+                let document = &self.documents[position];
+                Hit {
+                    target: document.target.clone(),
+                    title: document.title.clone(),
+                    snippet: snippet(&document.text, &terms),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,313 @@
+use crate::persist;
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+// Credentials can stay out of config.toml by naming where to get them instead: the output of a
+// command such as `pass show mail/work`, or an entry in a passphrase-encrypted vault file.
+// Whatever needs a password or key asks Secret::reveal for it at the moment it connects.
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Secret {
+    Plain(String),
+    Command { command: String },
+    Vault { vault: String },
+}
+
+impl Default for Secret {
+    fn default() -> Self {
+        Secret::Plain(String::new())
+    }
+}
+
+// Commands and vault entry names are not secret, plain values are
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Secret::Plain(value) if value.is_empty() => f.write_str("Plain(\"\")"),
+            Secret::Plain(_) => f.write_str("Plain(<redacted>)"),
+            Secret::Command { command } => write!(f, "Command({:?})", command),
+            Secret::Vault { vault } => write!(f, "Vault({:?})", vault),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Plain,
+    Command,
+    Vault,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Plain, Kind::Command, Kind::Vault];
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Plain => "Saved in config",
+            Kind::Command => "Command",
+            Kind::Vault => "Vault",
+        })
+    }
+}
+
+impl Secret {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Secret::Plain(_) => Kind::Plain,
+            Secret::Command { .. } => Kind::Command,
+            Secret::Vault { .. } => Kind::Vault,
+        }
+    }
+
+    // The value, command or vault entry name, depending on the kind
+    pub fn text(&self) -> &str {
+        match self {
+            Secret::Plain(value) => value,
+            Secret::Command { command } => command,
+            Secret::Vault { vault } => vault,
+        }
+    }
+
+    pub fn with_text(&self, text: String) -> Self {
+        match self {
+            Secret::Plain(_) => Secret::Plain(text),
+            Secret::Command { .. } => Secret::Command { command: text },
+            Secret::Vault { .. } => Secret::Vault { vault: text },
+        }
+    }
+
+    // Starts empty so a plain password is never carried into a command line
+    pub fn with_kind(&self, kind: Kind) -> Self {
+        if kind == self.kind() {
+            return self.clone();
+        }
+
+        match kind {
+            Kind::Plain => Secret::Plain(String::new()),
+            Kind::Command => Secret::Command {
+                command: String::new(),
+            },
+            Kind::Vault => Secret::Vault {
+                vault: String::new(),
+            },
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text().trim().is_empty()
+    }
+
+    pub fn reveal(&self) -> Result<String, String> {
+        match self {
+            Secret::Plain(value) => Ok(value.clone()),
+            Secret::Command { command } => run_command(command),
+            Secret::Vault { vault } => vault_get(vault),
+        }
+    }
+}
+
+// Like pass, most tools print the secret on the first line. Output never ends up in an error.
+fn run_command(command: &str) -> Result<String, String> {
+    #[cfg(windows)]
+    let output = Command::new("cmd").args(["/C", command]).output();
+    #[cfg(not(windows))]
+    let output = Command::new("sh").args(["-c", command]).output();
+
+    let output = output.map_err(|e| format!("Failed to run \"{}\": {}", command, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().next().unwrap_or_default();
+        return Err(
+            format!("\"{}\" failed ({}) {}", command, output.status, reason)
+                .trim_end()
+                .to_string(),
+        );
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|_| format!("\"{}\" printed something that is not text", command))?;
+    match stdout.lines().next() {
+        Some(secret) if !secret.is_empty() => Ok(secret.to_string()),
+        _ => Err(format!("\"{}\" printed nothing", command)),
+    }
+}
+
+// The vault is a JSON map of entry names to secrets, sealed with ChaCha20-Poly1305 under a key
+// derived from the passphrase with Argon2id
+
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Only ever held in memory, for the rest of the session
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn vault_path() -> Result<PathBuf, String> {
+    let project_dirs = ProjectDirs::from("com", "Apex", "tit-babbler")
+        .ok_or("Could not determine project directory")?;
+    let config_dir = project_dirs.config_dir();
+
+    fs::create_dir_all(config_dir)
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+
+    Ok(config_dir.join("vault.json"))
+}
+
+pub fn vault_exists() -> bool {
+    vault_path().is_ok_and(|path| path.exists())
+}
+
+fn passphrase() -> Result<String, String> {
+    if let Some(passphrase) = PASSPHRASE.lock().ok().and_then(|p| p.clone()) {
+        return Ok(passphrase);
+    }
+
+    env::var("TIT_VAULT_PASSPHRASE").map_err(|_| {
+        String::from("The vault is locked, enter its passphrase or set TIT_VAULT_PASSPHRASE")
+    })
+}
+
+pub fn is_unlocked() -> bool {
+    passphrase().is_ok()
+}
+
+// Checks the passphrase against the vault, or adopts it for a vault not created yet
+pub fn unlock(passphrase: String) -> Result<(), String> {
+    open(&passphrase)?;
+
+    if let Ok(mut slot) = PASSPHRASE.lock() {
+        *slot = Some(passphrase);
+    }
+
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) {
+        return Err(String::from("Vault file is damaged"));
+    }
+
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| String::from("Vault file is damaged"))
+        })
+        .collect()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, String> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive vault key: {}", e))?;
+
+    Ok(key)
+}
+
+fn open(passphrase: &str) -> Result<BTreeMap<String, String>, String> {
+    let path = vault_path()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+    };
+
+    let file = serde_json::from_str::<VaultFile>(&content)
+        .map_err(|e| format!("Vault file is damaged: {}", e))?;
+    if file.version != 1 {
+        return Err(format!("Unsupported vault version {}", file.version));
+    }
+
+    let key = derive_key(passphrase, &unhex(&file.salt)?)?;
+    let nonce = unhex(&file.nonce)?;
+    if nonce.len() != 12 {
+        return Err(String::from("Vault file is damaged"));
+    }
+
+    let plaintext = ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(&nonce), unhex(&file.ciphertext)?.as_ref())
+        .map_err(|_| String::from("Wrong vault passphrase, or the vault file is damaged"))?;
+
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Vault file is damaged: {}", e))
+}
+
+// Every write uses a fresh salt and nonce
+fn seal(passphrase: &str, entries: &BTreeMap<String, String>) -> Result<(), String> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt)?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let plaintext =
+        serde_json::to_vec(entries).map_err(|e| format!("Failed to serialize vault: {}", e))?;
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(&nonce, plaintext.as_ref())
+        .map_err(|_| String::from("Failed to encrypt vault"))?;
+
+    let file = VaultFile {
+        version: 1,
+        salt: hex(&salt),
+        nonce: hex(&nonce),
+        ciphertext: hex(&ciphertext),
+    };
+    let json = serde_json::to_string_pretty(&file)
+        .map_err(|e| format!("Failed to serialize vault: {}", e))?;
+
+    let path = vault_path()?;
+    persist::write_private(&path, json.as_bytes())
+}
+
+pub fn vault_get(name: &str) -> Result<String, String> {
+    open(&passphrase()?)?
+        .remove(name)
+        .ok_or_else(|| format!("The vault has no entry \"{}\"", name))
+}
+
+pub fn vault_set(name: &str, value: String) -> Result<(), String> {
+    let passphrase = passphrase()?;
+    let mut entries = open(&passphrase)?;
+    entries.insert(name.to_string(), value);
+
+    seal(&passphrase, &entries)
+}
+
+pub fn vault_remove(name: &str) -> Result<bool, String> {
+    let passphrase = passphrase()?;
+    let mut entries = open(&passphrase)?;
+    let removed = entries.remove(name).is_some();
+
+    if removed {
+        seal(&passphrase, &entries)?;
+    }
+
+    Ok(removed)
+}
+
+pub fn vault_names() -> Result<Vec<String>, String> {
+    Ok(open(&passphrase()?)?.into_keys().collect())
+}
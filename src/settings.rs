@@ -0,0 +1,649 @@
+use crate::config::{self, Config, Layout, Profile, Provider, TextSize, ThemeName};
+use crate::history;
+use crate::secrets::{self, Kind, Secret};
+use crate::{Message, fonts, hint_text, prompt, secondary_text, warning_text};
+use iced::widget::{button, checkbox, column, pick_list, row, text, text_editor, text_input};
+use iced::{Element, Length, Theme};
+
+// The settings screen edits a copy of the config; the live one is only replaced on save
+
+// Stands for an empty font in the family list
+const DEFAULT_FONT: &str = "Default";
+
+#[derive(Debug, Clone, Default)]
+pub enum Test {
+    #[default]
+    Idle,
+    Running,
+    Done(Result<String, String>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Edit {
+    SelectProfile(String),
+    NewProfileName(String),
+    AddProfile,
+    DeleteProfile,
+    Server(String),
+    Port(String),
+    Username(String),
+    Password(String),
+    PasswordKind(Kind),
+    PasswordValue(String),
+    Folders(String),
+    Provider(Provider),
+    Model(String),
+    ApiKey(String),
+    ApiKeyKind(Kind),
+    ApiKeyValue(String),
+    VaultPassphrase(String),
+    OllamaUrl(String),
+    Kind(history::Kind),
+    Template(text_editor::Action),
+    Schedule(String),
+    WindowHours(String),
+    Delta(bool),
+    Theme(ThemeName),
+    Font(String),
+    TextSize(TextSize),
+    Layout(Layout),
+}
+
+#[derive(Debug, Clone)]
+pub struct Form {
+    pub config: Config,
+    // Profile being edited, which becomes the active one on save
+    pub profile: String,
+    new_profile: String,
+    port: String,
+    folders: String,
+    // Kind of briefing whose prompt, schedule and window are shown
+    kind: history::Kind,
+    template: text_editor::Content,
+    window_hours: String,
+    // Values typed for vault entries, stored in the vault on save
+    password_value: String,
+    api_key_value: String,
+    vault_passphrase: String,
+    pub imap_test: Test,
+    pub llm_test: Test,
+}
+
+impl Form {
+    pub fn new(config: &Config, profile: &str) -> Self {
+        let mut form = Self {
+            config: config.clone(),
+            profile: profile.to_string(),
+            new_profile: String::new(),
+            port: String::new(),
+            folders: String::new(),
+            kind: history::Kind::default(),
+            template: text_editor::Content::new(),
+            window_hours: config.briefings.morning.window_hours.to_string(),
+            password_value: String::new(),
+            api_key_value: String::new(),
+            vault_passphrase: String::new(),
+            imap_test: Test::Idle,
+            llm_test: Test::Idle,
+        };
+        form.select(profile.to_string());
+
+        form
+    }
+
+    fn current(&mut self) -> &mut Profile {
+        self.config
+            .profiles
+            .entry(self.profile.clone())
+            .or_default()
+    }
+
+    // Loads the text fields from the profile so they can be edited as typed
+    fn select(&mut self, name: String) {
+        self.profile = name;
+        let profile = self.current().clone();
+
+        self.port = profile.account.port.to_string();
+        self.folders = profile.account.folders.join(", ");
+        self.template = text_editor::Content::with_text(profile.template(self.kind));
+    }
+
+    fn select_kind(&mut self, kind: history::Kind) {
+        self.kind = kind;
+        self.window_hours = self.config.briefings.get(kind).window_hours.to_string();
+        let profile = self.current().clone();
+        self.template = text_editor::Content::with_text(profile.template(kind));
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        self.config.problems(&self.profile)
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        self.config
+            .resolve(&self.profile)
+            .map(|profile| profile.warnings())
+            .unwrap_or_default()
+    }
+
+    fn vault_entries(&self) -> Vec<(&str, &str)> {
+        let Some(profile) = self.config.profiles.get(&self.profile) else {
+            return Vec::new();
+        };
+
+        [
+            (&profile.account.password, self.password_value.as_str()),
+            (&profile.llm.api_key, self.api_key_value.as_str()),
+        ]
+        .into_iter()
+        .filter(|(secret, _)| secret.kind() == Kind::Vault)
+        .map(|(secret, value)| (secret.text(), value))
+        .collect()
+    }
+
+    fn unlock_vault(&self) -> Result<(), String> {
+        if self.vault_passphrase.is_empty() {
+            return Ok(());
+        }
+
+        secrets::unlock(self.vault_passphrase.clone())
+    }
+
+    // Runs before the config is saved so it never names an entry the vault lacks
+    pub fn store_secrets(&self) -> Result<(), String> {
+        self.unlock_vault()?;
+
+        for (name, value) in self.vault_entries() {
+            if !value.is_empty() {
+                secrets::vault_set(name, value.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The edited profile as a connection test should see it, including values not yet in the vault
+    pub fn test_profile(&self) -> Result<Profile, String> {
+        self.unlock_vault()?;
+        let mut profile = self.config.resolve(&self.profile)?;
+
+        if profile.account.password.kind() == Kind::Vault && !self.password_value.is_empty() {
+            profile.account.password = Secret::Plain(self.password_value.clone());
+        }
+        if profile.llm.api_key.kind() == Kind::Vault && !self.api_key_value.is_empty() {
+            profile.llm.api_key = Secret::Plain(self.api_key_value.clone());
+        }
+
+        Ok(profile)
+    }
+
+    pub fn apply(&mut self, edit: Edit) {
+        match edit {
+            Edit::SelectProfile(name) => self.select(name),
+            Edit::NewProfileName(name) => {
+                self.new_profile = name;
+                return;
+            }
+            Edit::PasswordValue(value) => self.password_value = value,
+            Edit::ApiKeyValue(value) => self.api_key_value = value,
+            Edit::VaultPassphrase(passphrase) => self.vault_passphrase = passphrase,
+            Edit::Kind(kind) => {
+                self.select_kind(kind);
+                return;
+            }
+            Edit::AddProfile => {
+                let name = self.new_profile.trim().to_string();
+                if name.is_empty() || self.config.profiles.contains_key(&name) {
+                    return;
+                }
+                self.new_profile.clear();
+                self.select(name);
+            }
+            Edit::DeleteProfile => {
+                if self.config.profiles.len() < 2 {
+                    return;
+                }
+                self.config.profiles.remove(&self.profile);
+                self.select(self.config.active_name());
+            }
+            edit => self.edit_profile(edit),
+        }
+
+        // A result no longer says anything about the edited settings
+        self.imap_test = Test::Idle;
+        self.llm_test = Test::Idle;
+    }
+
+    fn edit_profile(&mut self, edit: Edit) {
+        let profile = self
+            .config
+            .profiles
+            .entry(self.profile.clone())
+            .or_default();
+        let account = &mut profile.account;
+        let llm = &mut profile.llm;
+
+        match edit {
+            Edit::Server(server) => account.server = server.trim().to_string(),
+            Edit::Port(port) => {
+                // An unparsable port becomes 0, which validation reports
+                account.port = port.trim().parse().unwrap_or(0);
+                self.port = port;
+            }
+            Edit::Username(username) => account.username = username.trim().to_string(),
+            Edit::Password(password) => account.password = account.password.with_text(password),
+            Edit::PasswordKind(kind) => account.password = account.password.with_kind(kind),
+            Edit::Folders(folders) => {
+                account.folders = config::split_folders(&folders);
+                self.folders = folders;
+            }
+            Edit::Provider(provider) => llm.provider = provider,
+            Edit::Model(model) => llm.model = model.trim().to_string(),
+            Edit::ApiKey(api_key) => {
+                llm.api_key = llm.api_key.with_text(api_key.trim().to_string())
+            }
+            Edit::ApiKeyKind(kind) => llm.api_key = llm.api_key.with_kind(kind),
+            Edit::OllamaUrl(url) => llm.ollama_url = url.trim().to_string(),
+            Edit::Template(action) => {
+                let is_edit = action.is_edit();
+                self.template.perform(action);
+
+                // Keeping the built-in prompt unsaved lets it improve with later versions
+                if is_edit {
+                    let template = self.template.text();
+                    *profile.template_mut(self.kind) =
+                        if template.trim() == prompt::template(self.kind).trim() {
+                            String::new()
+                        } else {
+                            template
+                        };
+                }
+            }
+            Edit::Schedule(schedule) => {
+                self.config.briefings.get_mut(self.kind).schedule = schedule
+            }
+            Edit::WindowHours(hours) => {
+                // Like the port, an unparsable window becomes 0 and validation reports it
+                self.config.briefings.get_mut(self.kind).window_hours =
+                    hours.trim().parse().unwrap_or(0);
+                self.window_hours = hours;
+            }
+            Edit::Delta(delta) => self.config.delta = delta,
+            Edit::Theme(theme) => self.config.theme = theme,
+            Edit::Font(font) => {
+                self.config.font = if font == DEFAULT_FONT {
+                    String::new()
+                } else {
+                    font
+                }
+            }
+            Edit::TextSize(size) => self.config.text_size = size,
+            Edit::Layout(layout) => self.config.layout = layout,
+            Edit::SelectProfile(_)
+            | Edit::NewProfileName(_)
+            | Edit::AddProfile
+            | Edit::DeleteProfile
+            | Edit::Kind(_)
+            | Edit::PasswordValue(_)
+            | Edit::ApiKeyValue(_)
+            | Edit::VaultPassphrase(_) => {}
+        }
+    }
+}
+
+fn labelled<'a>(
+    label: &'static str,
+    field: impl Into<Element<'a, Message>>,
+) -> Element<'a, Message> {
+    row![
+        text(label)
+            .font(fonts::body())
+            .size(fonts::scaled(12))
+            .style(secondary_text)
+            .width(Length::Fixed(110.0)),
+        field.into(),
+    ]
+    .spacing(8)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
+
+fn field<'a>(
+    placeholder: &'static str,
+    value: &'a str,
+    edit: fn(String) -> Edit,
+) -> text_input::TextInput<'a, Message> {
+    text_input(placeholder, value)
+        .font(fonts::body())
+        .size(fonts::scaled(13))
+        .on_input(move |value| Message::SettingsEdited(edit(value)))
+}
+
+fn input<'a>(
+    label: &'static str,
+    placeholder: &'static str,
+    value: &'a str,
+    edit: fn(String) -> Edit,
+) -> Element<'a, Message> {
+    labelled(label, field(placeholder, value, edit))
+}
+
+struct SecretEdits {
+    kind: fn(Kind) -> Edit,
+    text: fn(String) -> Edit,
+    value: fn(String) -> Edit,
+}
+
+// Where the credential comes from, then its value, command or vault entry name
+fn secret<'a>(
+    label: &'static str,
+    secret: &'a Secret,
+    value: &'a str,
+    edits: SecretEdits,
+) -> Element<'a, Message> {
+    let SecretEdits {
+        kind,
+        text: edit,
+        value: store,
+    } = edits;
+    let (placeholder, hidden) = match secret.kind() {
+        Kind::Plain => ("", true),
+        Kind::Command => ("pass show mail/work", false),
+        Kind::Vault => ("Entry name", false),
+    };
+
+    labelled(
+        label,
+        row![
+            pick_list(Kind::ALL, Some(secret.kind()), move |choice| {
+                Message::SettingsEdited(kind(choice))
+            })
+            .font(fonts::body())
+            .text_size(fonts::scaled(13)),
+            field(placeholder, secret.text(), edit).secure(hidden),
+            (secret.kind() == Kind::Vault)
+                .then(|| field("New value to store", value, store).secure(true)),
+        ]
+        .spacing(8),
+    )
+}
+
+fn heading(title: &'static str) -> Element<'static, Message> {
+    text(title)
+        .font(fonts::body())
+        .size(fonts::scaled(14))
+        .into()
+}
+
+fn test_row(test: &Test, message: Message) -> Element<'_, Message> {
+    let status = match test {
+        Test::Idle => text(""),
+        Test::Running => text("Testing...").style(hint_text),
+        Test::Done(Ok(detail)) => {
+            text(format!("✓ {}", detail)).style(|theme: &Theme| text::Style {
+                color: Some(theme.palette().success),
+            })
+        }
+        Test::Done(Err(e)) => text(format!("✗ {}", e)).style(|theme: &Theme| text::Style {
+            color: Some(theme.palette().danger),
+        }),
+    };
+
+    labelled(
+        "",
+        row![
+            button(
+                text("Test connection")
+                    .font(fonts::body())
+                    .size(fonts::scaled(12))
+            )
+            .on_press_maybe((!matches!(test, Test::Running)).then_some(message)),
+            status.font(fonts::body()).size(fonts::scaled(12)),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center),
+    )
+}
+
+pub fn view(form: &Form) -> Element<'_, Message> {
+    let config = &form.config;
+    // The form always keeps an entry for the profile it edits
+    let Some(profile) = config.profiles.get(&form.profile) else {
+        return text("").into();
+    };
+    let problems = form.problems();
+    let valid = problems.is_empty();
+
+    let provider_fields = match profile.llm.provider {
+        Provider::Gemini => secret(
+            "API key",
+            &profile.llm.api_key,
+            &form.api_key_value,
+            SecretEdits {
+                kind: Edit::ApiKeyKind,
+                text: Edit::ApiKey,
+                value: Edit::ApiKeyValue,
+            },
+        ),
+        Provider::Ollama => input(
+            "Ollama URL",
+            "http://localhost:11434",
+            &profile.llm.ollama_url,
+            Edit::OllamaUrl,
+        ),
+    };
+
+    let new_name = form.new_profile.trim();
+    let profiles = labelled(
+        "Profile",
+        row![
+            pick_list(config.profile_names(), Some(form.profile.clone()), |name| {
+                Message::SettingsEdited(Edit::SelectProfile(name))
+            })
+            .font(fonts::body())
+            .text_size(fonts::scaled(13)),
+            text_input("New profile", &form.new_profile)
+                .font(fonts::body())
+                .size(fonts::scaled(13))
+                .on_input(|name| Message::SettingsEdited(Edit::NewProfileName(name)))
+                .on_submit(Message::SettingsEdited(Edit::AddProfile)),
+            button(text("Add").font(fonts::body()).size(fonts::scaled(12))).on_press_maybe(
+                (!new_name.is_empty() && !config.profiles.contains_key(new_name))
+                    .then_some(Message::SettingsEdited(Edit::AddProfile))
+            ),
+            button(text("Delete").font(fonts::body()).size(fonts::scaled(12)))
+                .style(button::danger)
+                .on_press_maybe(
+                    (config.profiles.len() > 1)
+                        .then_some(Message::SettingsEdited(Edit::DeleteProfile))
+                ),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center),
+    );
+
+    let vault = (!form.vault_entries().is_empty() && !secrets::is_unlocked()).then(|| {
+        let placeholder = if secrets::vault_exists() {
+            "Passphrase"
+        } else {
+            "Passphrase for the new vault"
+        };
+        labelled(
+            "Vault",
+            field(placeholder, &form.vault_passphrase, Edit::VaultPassphrase).secure(true),
+        )
+    });
+
+    let overrides = config::env_overrides();
+    let overrides = (!overrides.is_empty()).then(|| {
+        text(format!(
+            "Set in the environment, overriding the fields below: {}",
+            overrides.join(", ")
+        ))
+        .font(fonts::body())
+        .size(fonts::scaled(12))
+        .style(hint_text)
+    });
+
+    let font = if config.font.is_empty() {
+        String::from(DEFAULT_FONT)
+    } else {
+        config.font.clone()
+    };
+    let families = [DEFAULT_FONT, fonts::FALLBACK]
+        .into_iter()
+        .map(String::from)
+        .chain(fonts::installed().iter().cloned())
+        .collect::<Vec<String>>();
+    // A family picked on another machine may not be installed here
+    let missing_font = (!families.contains(&font)).then(|| {
+        text(format!("Not installed, using {}", fonts::FALLBACK))
+            .font(fonts::body())
+            .size(fonts::scaled(12))
+            .style(hint_text)
+    });
+
+    let problems = column(problems.into_iter().map(|problem| {
+        text(problem)
+            .font(fonts::body())
+            .size(fonts::scaled(12))
+            .style(warning_text)
+            .into()
+    }))
+    .extend(form.warnings().into_iter().map(|warning| {
+        text(warning)
+            .font(fonts::body())
+            .size(fonts::scaled(12))
+            .style(hint_text)
+            .into()
+    }))
+    .spacing(2);
+
+    column![
+        text("Settings").font(fonts::body()).size(fonts::scaled(18)),
+        profiles,
+        overrides,
+        vault,
+        heading("Mail account"),
+        input(
+            "IMAP server",
+            "imap.example.com",
+            &profile.account.server,
+            Edit::Server
+        ),
+        input("Port", "993", &form.port, Edit::Port),
+        input(
+            "Username",
+            "you@example.com",
+            &profile.account.username,
+            Edit::Username
+        ),
+        secret(
+            "Password",
+            &profile.account.password,
+            &form.password_value,
+            SecretEdits {
+                kind: Edit::PasswordKind,
+                text: Edit::Password,
+                value: Edit::PasswordValue,
+            },
+        ),
+        input("Folders", "INBOX, Projects", &form.folders, Edit::Folders),
+        test_row(&form.imap_test, Message::TestImap),
+        heading("Assistant"),
+        labelled(
+            "Provider",
+            pick_list(Provider::ALL, Some(profile.llm.provider), |provider| {
+                Message::SettingsEdited(Edit::Provider(provider))
+            })
+            .font(fonts::body())
+            .text_size(fonts::scaled(13)),
+        ),
+        input("Model", "gemini-2.5-flash", &profile.llm.model, Edit::Model),
+        provider_fields,
+        test_row(&form.llm_test, Message::TestLlm),
+        heading("Briefing"),
+        labelled(
+            "Kind",
+            pick_list(history::Kind::ALL, Some(form.kind), |kind| {
+                Message::SettingsEdited(Edit::Kind(kind))
+            })
+            .font(fonts::body())
+            .text_size(fonts::scaled(13)),
+        ),
+        labelled(
+            "Prompt",
+            text_editor(&form.template)
+                .font(fonts::body())
+                .size(fonts::scaled(12))
+                .height(Length::Fixed(200.0))
+                .on_action(|action| Message::SettingsEdited(Edit::Template(action))),
+        ),
+        input(
+            "Schedule",
+            "mon-fri 07:30; sat 09:00",
+            &config.briefings.get(form.kind).schedule,
+            Edit::Schedule
+        ),
+        input(
+            "Window (hours)",
+            "16",
+            &form.window_hours,
+            Edit::WindowHours
+        ),
+        labelled(
+            "Changes only",
+            checkbox(config.delta)
+                .label("Report only what changed since the previous briefing")
+                .font(fonts::body())
+                .text_size(fonts::scaled(13))
+                .on_toggle(|delta| Message::SettingsEdited(Edit::Delta(delta))),
+        ),
+        heading("Appearance"),
+        labelled(
+            "Theme",
+            pick_list(ThemeName::ALL, Some(config.theme), |theme| {
+                Message::SettingsEdited(Edit::Theme(theme))
+            })
+            .font(fonts::body())
+            .text_size(fonts::scaled(13)),
+        ),
+        labelled(
+            "Font",
+            row![
+                pick_list(families, Some(font), |font| {
+                    Message::SettingsEdited(Edit::Font(font))
+                })
+                .font(fonts::body())
+                .text_size(fonts::scaled(13)),
+                pick_list(TextSize::ALL, Some(config.text_size), |size| {
+                    Message::SettingsEdited(Edit::TextSize(size))
+                })
+                .font(fonts::body())
+                .text_size(fonts::scaled(13)),
+                missing_font,
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+        ),
+        labelled(
+            "Layout",
+            pick_list(Layout::ALL, Some(config.layout), |layout| {
+                Message::SettingsEdited(Edit::Layout(layout))
+            })
+            .font(fonts::body())
+            .text_size(fonts::scaled(13)),
+        ),
+        problems,
+        row![
+            button(text("Save").font(fonts::body()).size(fonts::scaled(12)))
+                .on_press_maybe(valid.then_some(Message::SaveSettings)),
+            button(text("Cancel").font(fonts::body()).size(fonts::scaled(12)))
+                .style(button::secondary)
+                .on_press(Message::CloseSettings),
+        ]
+        .spacing(8),
+    ]
+    .spacing(10)
+    .into()
+}
@@ -0,0 +1,295 @@
+use crate::config::Account;
+use crate::export;
+use crate::history::Briefing;
+use crate::secrets::{self, Secret};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+
+// Delivery is enabled by setting BRIEFING_RECIPIENTS. Server credentials default to the IMAP
+// account; SMTP_SECURITY=none with a local port talks to a development sink such as MailHog.
+// Like the other credentials, the password can come from SMTP_PASSWORD, a command in
+// SMTP_PASSWORD_COMMAND or a vault entry named in SMTP_PASSWORD_VAULT.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Security {
+    StartTls,
+    Tls,
+    None,
+}
+
+struct Settings {
+    server: String,
+    port: u16,
+    security: Security,
+    credentials: Option<Credentials>,
+    from: Mailbox,
+    recipients: Vec<Mailbox>,
+}
+
+fn recipients() -> Vec<String> {
+    env::var("BRIEFING_RECIPIENTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|recipient| !recipient.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn enabled() -> bool {
+    !recipients().is_empty()
+}
+
+fn password() -> Option<Secret> {
+    let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+    var("SMTP_PASSWORD")
+        .map(Secret::Plain)
+        .or_else(|| var("SMTP_PASSWORD_COMMAND").map(|command| Secret::Command { command }))
+        .or_else(|| var("SMTP_PASSWORD_VAULT").map(|vault| Secret::Vault { vault }))
+}
+
+// So the vault can be unlocked before delivery needs it
+pub fn uses_vault() -> bool {
+    enabled() && password().is_some_and(|password| password.kind() == secrets::Kind::Vault)
+}
+
+// The account supplies the credentials and sender when the SMTP_ variables leave them out
+fn settings(account: Account) -> Result<Settings, String> {
+    let security = match env::var("SMTP_SECURITY")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "" | "starttls" => Security::StartTls,
+        "tls" | "ssl" => Security::Tls,
+        "none" => Security::None,
+        other => return Err(format!("Unknown SMTP_SECURITY \"{}\"", other)),
+    };
+
+    let server = env::var("SMTP_SERVER").map_err(|_| String::from("SMTP_SERVER not set"))?;
+    let port = match env::var("SMTP_PORT") {
+        Ok(port) => port
+            .parse()
+            .map_err(|_| format!("Invalid SMTP_PORT \"{}\"", port))?,
+        Err(_) => match security {
+            Security::StartTls => 587,
+            Security::Tls => 465,
+            Security::None => 25,
+        },
+    };
+
+    // Never hand the IMAP password to an unencrypted server unless asked to explicitly
+    let username = match (env::var("SMTP_USERNAME"), security) {
+        (Ok(username), _) => username,
+        (Err(_), Security::None) => String::new(),
+        (Err(_), _) => account.username.clone(),
+    };
+    let credentials = if username.is_empty() {
+        None
+    } else {
+        let password = password().unwrap_or(account.password).reveal()?;
+        Some(Credentials::new(username.clone(), password))
+    };
+
+    let from = env::var("SMTP_FROM")
+        .ok()
+        .or_else(|| Some(username).filter(|u| u.contains('@')))
+        .or_else(|| Some(account.username).filter(|u| !u.is_empty()))
+        .ok_or("SMTP_FROM not set")?;
+    let from = from
+        .parse::<Mailbox>()
+        .map_err(|e| format!("Invalid sender \"{}\": {}", from, e))?;
+
+    let recipients = recipients()
+        .iter()
+        .map(|recipient| {
+            recipient
+                .parse::<Mailbox>()
+                .map_err(|e| format!("Invalid recipient \"{}\": {}", recipient, e))
+        })
+        .collect::<Result<Vec<Mailbox>, String>>()?;
+
+    Ok(Settings {
+        server,
+        port,
+        security,
+        credentials,
+        from,
+        recipients,
+    })
+}
+
+fn transport(settings: &Settings) -> Result<AsyncSmtpTransport<Tokio1Executor>, String> {
+    let tls = match settings.security {
+        Security::None => Tls::None,
+        security => {
+            let parameters = TlsParameters::new(settings.server.clone())
+                .map_err(|e| format!("Failed to set up TLS: {}", e))?;
+            if security == Security::Tls {
+                Tls::Wrapper(parameters)
+            } else {
+                Tls::Required(parameters)
+            }
+        }
+    };
+
+    let mut builder =
+        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(settings.server.as_str())
+            .port(settings.port)
+            .tls(tls);
+    if let Some(credentials) = &settings.credentials {
+        builder = builder.credentials(credentials.clone());
+    }
+
+    Ok(builder.build())
+}
+
+pub async fn send_briefing(account: Account, briefing: Briefing) -> Result<usize, String> {
+    let settings = settings(account)?;
+
+    let mut message = Message::builder()
+        .from(settings.from.clone())
+        .subject(briefing.title());
+    for recipient in &settings.recipients {
+        message = message.to(recipient.clone());
+    }
+    let message = message
+        .multipart(MultiPart::alternative_plain_html(
+            export::text(&briefing),
+            export::html(&briefing),
+        ))
+        .map_err(|e| format!("Failed to build email: {}", e))?;
+
+    transport(&settings)?
+        .send(message)
+        .await
+        .map_err(|e| format!("Failed to send briefing: {}", e))?;
+
+    Ok(settings.recipients.len())
+}
+
+pub async fn check_connection(account: Account) -> Result<String, String> {
+    let settings = settings(account)?;
+
+    let reachable = transport(&settings)?
+        .test_connection()
+        .await
+        .map_err(|e| format!("Failed to connect to SMTP server: {}", e))?;
+
+    if reachable {
+        Ok(format!(
+            "{}:{} accepts connections",
+            settings.server, settings.port
+        ))
+    } else {
+        Err(format!(
+            "{}:{} is not responding",
+            settings.server, settings.port
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // Accepts one message the way a development sink would and returns its envelope and data
+    fn sink(listener: TcpListener) -> thread::JoinHandle<(Vec<String>, String)> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut envelope = Vec::new();
+            let mut data = String::new();
+
+            writer.write_all(b"220 localhost ESMTP sink\r\n").unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let command = line.trim_end().to_string();
+                let verb = command.to_uppercase();
+
+                if verb.starts_with("EHLO") || verb.starts_with("HELO") {
+                    writer.write_all(b"250 localhost\r\n").unwrap();
+                } else if verb.starts_with("DATA") {
+                    writer
+                        .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                        .unwrap();
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line == ".\r\n" {
+                            break;
+                        }
+                        data.push_str(&line);
+                    }
+                    writer.write_all(b"250 Queued\r\n").unwrap();
+                } else if verb.starts_with("QUIT") {
+                    writer.write_all(b"221 Bye\r\n").unwrap();
+                    break;
+                } else {
+                    envelope.push(command);
+                    writer.write_all(b"250 OK\r\n").unwrap();
+                }
+            }
+
+            (envelope, data)
+        })
+    }
+
+    #[tokio::test]
+    async fn sends_briefing_to_local_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = sink(listener);
+
+        // The only test reading these variables, so nothing else races on them
+        unsafe {
+            env::set_var("SMTP_SERVER", "127.0.0.1");
+            env::set_var("SMTP_PORT", port.to_string());
+            env::set_var("SMTP_SECURITY", "none");
+            env::set_var("SMTP_FROM", "briefings@example.com");
+            env::set_var("BRIEFING_RECIPIENTS", "ada@example.com, grace@example.com");
+            env::remove_var("SMTP_USERNAME");
+        }
+
+        let now = Local::now();
+        let briefing = Briefing {
+            id: 7,
+            kind: Default::default(),
+            created_at: now,
+            window_start: now,
+            window_end: now,
+            source_email_ids: Vec::new(),
+            model: String::from("offline"),
+            usage: None,
+            text: String::from("The quarterly report is due on Friday."),
+            cached: false,
+            degraded: None,
+            action_items: Vec::new(),
+            projects: Vec::new(),
+            meetings: None,
+        };
+
+        let recipients = send_briefing(Account::default(), briefing).await.unwrap();
+        let (envelope, data) = received.join().unwrap();
+
+        assert_eq!(recipients, 2);
+        assert!(envelope.contains(&String::from("MAIL FROM:<briefings@example.com>")));
+        assert!(envelope.contains(&String::from("RCPT TO:<ada@example.com>")));
+        assert!(envelope.contains(&String::from("RCPT TO:<grace@example.com>")));
+        assert!(data.contains("From: briefings@example.com"));
+        assert!(data.contains("Subject: "));
+        assert!(data.contains("The quarterly report is due on Friday."));
+    }
+}
@@ -0,0 +1,134 @@
+use chrono::{DateTime, Datelike, Local};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+// Token counts reported by the model for a single request
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub candidate_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl Usage {
+    // Thinking tokens are billed as output but not included in the candidate count
+    pub fn output_tokens(&self) -> u64 {
+        self.total_tokens
+            .saturating_sub(self.prompt_tokens)
+            .max(self.candidate_tokens)
+    }
+}
+
+// USD per million tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        let prices = [
+            ("gemini-2.5-pro", 1.25, 10.0),
+            ("gemini-2.5-flash", 0.30, 2.50),
+            ("gemini-2.5-flash-lite", 0.10, 0.40),
+        ]
+        .into_iter()
+        .map(|(model, input, output)| (model.to_string(), ModelPrice { input, output }))
+        .collect();
+
+        Self { prices }
+    }
+}
+
+impl PriceTable {
+    // Entries in prices.json override or extend the built-in table
+    pub fn load() -> Self {
+        let mut table = Self::default();
+
+        let Some(project_dirs) = ProjectDirs::from("com", "Apex", "tit-babbler") else {
+            return table;
+        };
+        let path = project_dirs.config_dir().join("prices.json");
+
+        if let Ok(content) = fs::read_to_string(path) {
+            match serde_json::from_str::<HashMap<String, ModelPrice>>(&content) {
+                Ok(prices) => table.prices.extend(prices),
+                Err(e) => eprintln!("Ignoring invalid prices.json: {}", e),
+            }
+        }
+
+        table
+    }
+
+    pub fn cost(&self, model: &str, usage: &Usage) -> f64 {
+        match self.prices.get(model) {
+            Some(price) => {
+                (usage.prompt_tokens as f64 * price.input
+                    + usage.output_tokens() as f64 * price.output)
+                    / 1_000_000.0
+            }
+            None => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub at: DateTime<Local>,
+    pub model: String,
+    pub usage: Usage,
+    pub cost: f64,
+}
+
+impl UsageRecord {
+    pub fn new(model: &str, usage: Usage) -> Self {
+        Self {
+            at: Local::now(),
+            model: model.to_string(),
+            cost: PriceTable::load().cost(model, &usage),
+            usage,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} tokens ({} in / {} out) · ${:.4}",
+            self.usage.total_tokens,
+            self.usage.prompt_tokens,
+            self.usage.output_tokens(),
+            self.cost
+        )
+    }
+}
+
+pub fn daily_total(log: &[UsageRecord]) -> f64 {
+    let today = Local::now().date_naive();
+
+    log.iter()
+        .filter(|r| r.at.date_naive() == today)
+        .map(|r| r.cost)
+        .sum()
+}
+
+pub fn monthly_total(log: &[UsageRecord]) -> f64 {
+    let now = Local::now();
+
+    log.iter()
+        .filter(|r| r.at.year() == now.year() && r.at.month() == now.month())
+        .map(|r| r.cost)
+        .sum()
+}
+
+pub fn monthly_budget() -> Option<f64> {
+    env::var("MONTHLY_BUDGET_USD")
+        .ok()
+        .and_then(|budget| budget.trim().parse().ok())
+}